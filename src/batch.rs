@@ -0,0 +1,156 @@
+//! Running many merge jobs, e.g., of a parameter scan.
+//! Errors of one job do not abort the others, instead a summary is printed at the end
+
+use std::{
+    collections::BTreeMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}},
+    thread
+};
+
+use crate::{parse_with_variables, read_input, template::resolve, JobOverrides};
+
+/// A job of the batch
+#[derive(Debug, Clone)]
+pub struct BatchEntry
+{
+    pub path: String,
+    pub variables: BTreeMap<String, String>
+}
+
+impl BatchEntry
+{
+    fn name(&self) -> String
+    {
+        if self.variables.is_empty() {
+            format!("{:?}", self.path)
+        } else {
+            let vars: Vec<_> = self.variables.iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            format!("{:?} [{}]", self.path, vars.join(" "))
+        }
+    }
+
+    /// Merges the job and returns the output file
+    pub fn run(&self, overrides: &JobOverrides) -> String
+    {
+        let mut job = parse_with_variables(&self.path, &self.variables);
+        overrides.apply(&mut job);
+        job.work();
+        job.out
+    }
+}
+
+/// Parses NAME=VALUE
+pub fn parse_variable(var: &str) -> (String, String)
+{
+    match var.split_once('='){
+        Some((name, value)) => (name.to_owned(), value.to_owned()),
+        None => panic!("Invalid variable {:?}, use NAME=VALUE", var)
+    }
+}
+
+/// Every line of the manifest contains the path of a job file (relative to the manifest),
+/// optionally followed by variables, e.g., `job.json N=3200 dir=data`.
+/// Empty lines and lines starting with `#` are ignored.
+/// The variables of a line overwrite `variables`
+pub fn read_manifest(manifest: &str, variables: &BTreeMap<String, String>) -> Vec<BatchEntry>
+{
+    let content = read_input(manifest);
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(
+            |line|
+            {
+                let mut iter = line.split_whitespace();
+                let path = resolve(iter.next().unwrap(), manifest);
+                let mut variables = variables.clone();
+                variables.extend(iter.map(parse_variable));
+                BatchEntry{
+                    path,
+                    variables
+                }
+            }
+        ).collect()
+}
+
+/// Output file or error message. None if the job was not executed (yet)
+type JobResult = Option<Result<String, String>>;
+
+pub(crate) fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String
+{
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown error".to_owned()
+    }
+}
+
+/// Runs all jobs, `threads` of them in parallel, and prints a summary.
+/// Returns false if any of the jobs failed
+pub fn run_batch(entries: Vec<BatchEntry>, threads: usize, overrides: &JobOverrides) -> bool
+{
+    let total = entries.len();
+    let entries = Arc::new(entries);
+    let overrides = Arc::new(overrides.clone());
+    let next = Arc::new(AtomicUsize::new(0));
+    let results: Arc<Mutex<Vec<JobResult>>> = Arc::new(Mutex::new(vec![None; total]));
+
+    let handles: Vec<_> = (0..threads.clamp(1, total.max(1)))
+        .map(
+            |_|
+            {
+                let entries = entries.clone();
+                let next = next.clone();
+                let results = results.clone();
+                let overrides = overrides.clone();
+                thread::spawn(
+                    move ||
+                    {
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let entry = match entries.get(index){
+                                Some(e) => e,
+                                None => break
+                            };
+                            let result = catch_unwind(AssertUnwindSafe(|| entry.run(&overrides)))
+                                .map_err(panic_message);
+                            if let Ok(out) = &result {
+                                println!("Success! Output of {} written to {}", entry.name(), out);
+                            }
+                            results.lock().unwrap()[index] = Some(result);
+                        }
+                    }
+                )
+            }
+        ).collect();
+
+    for handle in handles
+    {
+        handle.join().unwrap();
+    }
+
+    let results = results.lock().unwrap();
+    let failed: Vec<_> = entries.iter()
+        .zip(results.iter())
+        .filter_map(
+            |(entry, result)|
+            match result{
+                Some(Ok(_)) => None,
+                Some(Err(e)) => Some((entry, e.as_str())),
+                None => Some((entry, "not executed"))
+            }
+        ).collect();
+
+    println!();
+    println!("Summary: {} of {} jobs merged successfully", total - failed.len(), total);
+    for (entry, error) in failed.iter()
+    {
+        println!("FAILED {}: {}", entry.name(), error);
+    }
+    failed.is_empty()
+}
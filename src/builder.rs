@@ -0,0 +1,138 @@
+//! Constructing a [Job] in code, e.g., to merge in-process at the end of a simulation
+//! instead of writing the intervals to files first
+
+use crate::*;
+
+/// Builder for a [Job]. Everything that is not set uses the same default 
+/// as a missing field in the json job file
+#[derive(Debug, Clone)]
+pub struct JobBuilder
+{
+    job: Job
+}
+
+impl Default for JobBuilder
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobBuilder
+{
+    /// Empty job. `out` is only required if you want to call [Job::work]
+    pub fn new() -> Self
+    {
+        Self{
+            job: Job{
+                schema: None,
+                include: None,
+                variables: None,
+                out: String::new(),
+                files: Vec::new(),
+                hist: HistType::default(),
+                merge: MergeType::default(),
+                global_comment: None,
+                bin_size: None,
+                bin_starting_point: None,
+                output_format: None,
+                only_merged: None,
+                aligned_out: None,
+                provenance: None,
+                carry_prefix: None,
+                normalization: None,
+                intervals: Vec::new()
+            }
+        }
+    }
+
+    /// Name of the output file used by [Job::work]
+    pub fn out<S: Into<String>>(mut self, out: S) -> Self
+    {
+        self.job.out = out.into();
+        self
+    }
+
+    /// Add a file, just like an entry of `files` in the job file
+    pub fn file(mut self, file: FileInfo) -> Self
+    {
+        self.job.files.push(file);
+        self
+    }
+
+    /// Add an interval that is already in memory. 
+    /// `log_prob` is the logarithm (base 10) of the probability, 
+    /// where `log_prob[0]` corresponds to the bin `left`
+    pub fn interval(mut self, left: isize, log_prob: Vec<f64>) -> Self
+    {
+        self.job.add_interval(left, log_prob, false);
+        self
+    }
+
+    /// Same as [JobBuilder::interval], but marks the interval as reference for the normalization
+    pub fn reference_interval(mut self, left: isize, log_prob: Vec<f64>) -> Self
+    {
+        self.job.add_interval(left, log_prob, true);
+        self
+    }
+
+    pub fn merge(mut self, merge: MergeType) -> Self
+    {
+        self.job.merge = merge;
+        self
+    }
+
+    pub fn global_comment(mut self, comment: Comment) -> Self
+    {
+        self.job.global_comment = Some(comment);
+        self
+    }
+
+    pub fn bin_size(mut self, bin_size: f64) -> Self
+    {
+        self.job.bin_size = Some(bin_size);
+        self
+    }
+
+    pub fn bin_starting_point(mut self, bin_starting_point: f64) -> Self
+    {
+        self.job.bin_starting_point = Some(bin_starting_point);
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self
+    {
+        self.job.normalization = Some(normalization);
+        self
+    }
+
+    pub fn output_format(mut self, format: OutputFormat) -> Self
+    {
+        self.job.output_format = Some(format);
+        self
+    }
+
+    pub fn only_merged(mut self, only_merged: bool) -> Self
+    {
+        self.job.only_merged = Some(only_merged);
+        self
+    }
+
+    pub fn aligned_out<S: Into<String>>(mut self, aligned_out: S) -> Self
+    {
+        self.job.aligned_out = Some(aligned_out.into());
+        self
+    }
+
+    pub fn provenance(mut self, provenance: bool) -> Self
+    {
+        self.job.provenance = Some(provenance);
+        self
+    }
+
+    pub fn build(mut self) -> Job
+    {
+        self.job.prepare_files();
+        self.job
+    }
+}
@@ -0,0 +1,79 @@
+//! Reading the json serialized WangLandau or Rewl state of the `sampling` crate.
+//! As the ensemble is unknown here, the json is not deserialized into
+//! the actual structs, only the fields `log_density` and `hist` are extracted
+
+use serde_json::{from_str, Value};
+use sampling::HistIsizeFast;
+
+use crate::{FileInfo, read_input};
+
+fn get_walkers(json: &Value) -> Vec<&Value>
+{
+    // Rewl and Rees store their walkers in the array "walker",
+    // while a single WangLandau simulation stores everything top level
+    match json.get("walker")
+    {
+        Some(Value::Array(walkers)) => walkers.iter().collect(),
+        _ => vec![json]
+    }
+}
+
+fn log_density_base10(walker: &Value, info: &FileInfo) -> Vec<f64>
+{
+    let log_density = match walker.get("log_density"){
+        Some(Value::Array(arr)) => arr,
+        _ => panic!("Checkpoint is missing array 'log_density' in {:?}", info)
+    };
+
+    // the sampling crate stores the natural logarithm
+    log_density.iter()
+        .map(
+            |val|
+            {
+                match val.as_f64(){
+                    Some(v) if v.is_finite() => v * std::f64::consts::LOG10_E,
+                    _ => f64::NAN
+                }
+            }
+        ).collect()
+}
+
+fn hist_left(walker: &Value, info: &FileInfo) -> isize
+{
+    walker.get("hist")
+        .and_then(|hist| hist.get("left"))
+        .and_then(Value::as_i64)
+        .unwrap_or_else(|| panic!("Checkpoint is missing 'hist' with integer 'left' in {:?}", info))
+        as isize
+}
+
+/// Returns the histogram and the logarithmic probability (base 10)
+/// for each of the `log_cols`, i.e., for each requested walker
+pub fn read_walkers(info: &FileInfo) -> (Vec<HistIsizeFast>, Vec<Vec<f64>>)
+{
+    let content = read_input(&info.path);
+
+    let json: Value = from_str(&content)
+        .expect("Invalid Json in checkpoint");
+
+    let walkers = get_walkers(&json);
+
+    let shift = info.shift.unwrap_or(0);
+
+    info.log_cols
+        .iter()
+        .map(
+            |col|
+            {
+                let walker = match walkers.get(col.index){
+                    Some(w) => w,
+                    None => panic!("Error, walker {:?} does not exist in {:?}. There are {} walkers", col.index, info, walkers.len())
+                };
+                let log = log_density_base10(walker, info);
+                let left = hist_left(walker, info) + shift;
+                let hist = HistIsizeFast::new(left, left + log.len() as isize)
+                    .expect("unable to create histogram");
+                (hist, log)
+            }
+        ).unzip()
+}
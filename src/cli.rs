@@ -0,0 +1,422 @@
+use structopt::StructOpt;
+use glob::Pattern;
+use std::str::FromStr;
+
+use crate::{MergeType, OutputFormat, Job, LogCol, log_cols::parse_range};
+
+#[derive(Debug, StructOpt, Clone)]
+#[structopt(about = "Merge your WangLandau Probabilities. You can also insert simple sampling probabilities")]
+pub enum Opt
+{
+    Merge(Merge),
+    LogColRange(LogColRange),
+    /// Prints an example json file. This json file is needed for the merging to specify what you want to merge
+    ExampleJson,
+    CreateJob(CreateJob),
+    /// Prints the JSON Schema of the job file, e.g., for autocompletion and validation in your editor
+    Schema
+}
+
+#[derive(Debug, Clone, StructOpt)]
+/// Merge logarithmic probability densitys from Wang Landau (or Entropic Sampling)
+pub struct Merge
+{
+    #[structopt(long, short, number_of_values = 1)]
+    /// path to json file, which specifies the merge job.
+    /// Files ending with .toml, .yaml or .yml are read as Toml or Yaml respectively.
+    /// Use "-" to read the job from stdin (as json).
+    /// Can be used multiple times to merge multiple jobs
+    pub json: Vec<String>,
+    #[structopt(long, short)]
+    /// File listing job files (relative to the manifest), one per line, 
+    /// optionally followed by variables, e.g., "job.json N=3200".
+    /// Lines starting with # are ignored
+    pub manifest: Option<String>,
+    #[structopt(long, short, default_value = "1")]
+    /// How many jobs are merged in parallel
+    pub threads: usize,
+    #[structopt(flatten)]
+    pub overrides: JobOverrides,
+    #[structopt(long = "var", number_of_values = 1)]
+    /// Set a variable of the job file, e.g., --var N=3200.
+    /// Overwrites the variables defined in the job file.
+    /// Can be used multiple times
+    pub variables: Vec<String>
+}
+
+/// Overwrite parts of the job without editing the job file
+#[derive(Debug, Clone, StructOpt, Default)]
+pub struct JobOverrides
+{
+    #[structopt(long)]
+    /// Overwrites `out` of the job
+    pub out: Option<String>,
+    #[structopt(long)]
+    /// Overwrites `merge` of the job: Average or Derivative
+    pub merge_type: Option<MergeType>,
+    #[structopt(long)]
+    /// Overwrites `bin_size` of the job
+    pub bin_size: Option<f64>,
+    #[structopt(long, allow_hyphen_values = true)]
+    /// Overwrites `bin_starting_point` of the job
+    pub bin_starting_point: Option<f64>,
+    #[structopt(long, number_of_values = 1)]
+    /// Do not merge the files whose path matches this (glob) pattern.
+    /// Can be used multiple times
+    pub exclude_file: Vec<String>,
+    #[structopt(long, number_of_values = 1)]
+    /// Do not merge this interval, given as PATH:COLUMN, where PATH can be a (glob) pattern.
+    /// Can be used multiple times
+    pub exclude_interval: Vec<String>
+}
+
+fn matches_path(pattern: &str, path: &str) -> bool
+{
+    pattern == path || Pattern::new(pattern)
+        .expect("Error in glob pattern")
+        .matches(path)
+}
+
+impl JobOverrides
+{
+    pub fn apply(&self, job: &mut Job)
+    {
+        if let Some(out) = &self.out {
+            job.out = out.clone();
+        }
+        if let Some(merge) = self.merge_type {
+            job.merge = merge;
+        }
+        if self.bin_size.is_some() {
+            job.bin_size = self.bin_size;
+        }
+        if self.bin_starting_point.is_some() {
+            job.bin_starting_point = self.bin_starting_point;
+        }
+
+        // excluded files and intervals are disabled instead of removed, so they are still documented in the provenance
+        for pattern in self.exclude_file.iter()
+        {
+            let mut found = false;
+            for file in job.files.iter_mut().filter(|f| matches_path(pattern, &f.path))
+            {
+                file.enabled = Some(false);
+                found = true;
+            }
+            if !found {
+                eprintln!("Warning: --exclude-file {:?} does not match any file", pattern);
+            }
+        }
+
+        for interval in self.exclude_interval.iter()
+        {
+            let (pattern, column) = match interval.rsplit_once(':'){
+                Some((pattern, column)) => {
+                    let column: usize = column.parse()
+                        .unwrap_or_else(|_| panic!("Invalid column in --exclude-interval {:?}", interval));
+                    (pattern, column)
+                },
+                None => panic!("Invalid --exclude-interval {:?}, use PATH:COLUMN", interval)
+            };
+            let mut found = false;
+            for file in job.files.iter_mut().filter(|f| matches_path(pattern, &f.path))
+            {
+                for col in file.log_cols.iter_mut().filter(|col| col.index == column)
+                {
+                    col.enabled = Some(false);
+                    found = true;
+                }
+                // "N.." is only expanded when the file is read, which keeps columns that are already present
+                let till_end = file.log_cols.iter()
+                    .any(|col| col.till_end.unwrap_or(false) && col.index < column);
+                if till_end && !file.log_cols.iter().any(|col| col.index == column) {
+                    let mut col = LogCol::new(column);
+                    col.enabled = Some(false);
+                    file.log_cols.push(col);
+                    found = true;
+                }
+            }
+            if !found {
+                eprintln!("Warning: --exclude-interval {:?} does not match any interval", interval);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, StructOpt)]
+/// As the json-array log_cols is a bit inconvinient,
+/// this helps in creating it. Try it out.
+pub struct LogColRange
+{
+    #[structopt(long, short)]
+    /// Leftest column
+    pub left: usize,
+
+    #[structopt(long, short)]
+    /// rightest column
+    pub right: usize,
+
+    #[structopt(long)]
+    /// trim left to be used everywhere
+    pub trim_left: Option<usize>,
+
+    #[structopt(long)]
+    /// trim right to be used everywhere
+    pub trim_right: Option<usize>,
+
+    #[structopt(long, allow_hyphen_values = true)]
+    /// min bin to be used everywhere
+    pub min_bin: Option<isize>,
+
+    #[structopt(long, allow_hyphen_values = true)]
+    /// max bin to be used everywhere
+    pub max_bin: Option<isize>,
+}
+
+/// For quickly creating the file list from a pattern
+#[derive(Debug, Clone, StructOpt)]
+pub struct CreateJob
+{
+    #[structopt(long, short)]
+    /// Pattern of all the files you want to include - uses globbing so, e.g., the wildcard * works.
+    /// The column options below apply to these files
+    pub globbing: Option<String>,
+
+    #[structopt(long, number_of_values = 1)]
+    /// Further files with their own options, given as pattern followed by KEY=VALUE pairs, e.g.,
+    /// --group 'SimpleSample*.dat hist=2 log=1 shift=-1'.
+    /// Keys: hist, log (e.g. 3, 3..27, 3..=26 or 3..), shift, comment, sep and missing.
+    /// What is not given is detected from the first file of the group.
+    /// Can be used multiple times
+    pub group: Vec<GlobGroup>,
+
+    #[structopt(long)]
+    /// Which column represents the histogram?
+    /// If ommited, it is detected from the first file.
+    /// If that fails (or with --no-detect), the line-number (without counting comments)
+    /// will be used as bin
+    pub hist_col_left: Option<usize>,
+
+    #[structopt(long)]
+    /// Currently not nessessary, in here for future expansions
+    pub hist_col_right: Option<usize>,
+
+    #[structopt(long)]
+    /// which columns contain the logarithmic probabilities? - left border.
+    /// If ommited, it is detected from the first file
+    pub log_col_left: Option<usize>,
+
+    #[structopt(long, short)]
+    /// rightest column, exclusive. Can be omitted if only one column should be considered
+    pub log_col_right: Option<usize>,
+
+    /// Start from log_col_left and figure out how many columns there are by 
+    /// reading the files in question.
+    /// Incompatible with option `log_col_right` 
+    #[structopt(long, short)]
+    pub end: bool,
+
+    /// Name of output file
+    #[structopt(long, short, default_value = "merged.out")]
+    pub out: String,
+
+    #[structopt(long, short, default_value = "Average")]
+    /// Which merge algorithm to use? Options: "Average" or "Derivative"
+    pub merge: MergeType,
+
+    #[structopt(long)]
+    /// Setting the global comment. If ommited, it is detected from the first file
+    pub global_comment: Option<String>,
+
+    #[structopt(long)]
+    /// Separator of the columns. If ommited, it is detected from the first file
+    /// (comma or whitespace)
+    pub sep: Option<String>,
+
+    #[structopt(long)]
+    /// Do not detect the options that were not specified from the first file
+    pub no_detect: bool,
+
+    #[structopt(long)]
+    /// Setting the bin size
+    pub bin_size: Option<f64>,
+
+    #[structopt(long, short)]
+    /// Setting the bin starting point
+    pub bin_starting_point: Option<f64>,
+
+    #[structopt(long, short)]
+    /// Instead of printing the json file to the terminal,
+    /// it will be written into the file instead.
+    /// Written as Toml or Yaml if the file ends with .toml, .yaml or .yml
+    pub job_file: Option<String>,
+
+    #[structopt(long, short)]
+    /// Used to shift the histograms
+    pub shift: Option<isize>,
+
+    #[structopt(long)]
+    /// What is the identifier for missing Values? Default: NaN
+    pub missing: Option<String>,
+
+    #[structopt(long)]
+    /// Format of the output file. Options: "Text" (default), "Csv", "Json" or "Npy"
+    pub output_format: Option<OutputFormat>
+}
+
+impl CreateJob
+{
+    pub fn make_valid(&mut self){
+        if self.end && self.log_col_right.is_some()
+        {
+            eprintln!("WARNING: Option log_col_write will be overwritten due to flag `end`");
+            self.log_col_right = None;
+        }
+    }
+
+    /// `--globbing` with the column options, followed by all `--group`s
+    pub fn groups(&self) -> Vec<GlobGroup>
+    {
+        let main = self.globbing
+            .as_ref()
+            .map(
+                |pattern|
+                GlobGroup{
+                    pattern: pattern.clone(),
+                    hist_col: self.hist_col_left,
+                    log_col_left: self.log_col_left,
+                    log_col_right: self.log_col_right,
+                    end: self.end,
+                    shift: self.shift,
+                    comment: self.global_comment.clone(),
+                    sep: self.sep.clone(),
+                    missing: self.missing.clone()
+                }
+            );
+        main.into_iter()
+            .chain(self.group.iter().cloned())
+            .collect()
+    }
+}
+
+/// Files matching a glob pattern, which share their columns
+#[derive(Debug, Clone, Default)]
+pub struct GlobGroup
+{
+    pub pattern: String,
+    pub hist_col: Option<usize>,
+    pub log_col_left: Option<usize>,
+    /// exclusive
+    pub log_col_right: Option<usize>,
+    /// log columns go until the last column
+    pub end: bool,
+    pub shift: Option<isize>,
+    pub comment: Option<String>,
+    pub sep: Option<String>,
+    pub missing: Option<String>
+}
+
+impl FromStr for GlobGroup
+{
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.split_whitespace();
+        let mut group = GlobGroup{
+            pattern: iter.next()
+                .ok_or("Empty group, use e.g. 'PATTERN log=3..27'")?
+                .to_owned(),
+            ..Default::default()
+        };
+        for option in iter
+        {
+            let (key, value) = option.split_once('=')
+                .ok_or_else(|| format!("Invalid option {:?} of group, use KEY=VALUE", option))?;
+            let invalid = |_| format!("Invalid value of {:?} in group", key);
+            match key{
+                "hist" => group.hist_col = Some(value.parse().map_err(invalid)?),
+                "log" => {
+                    let (left, right) = if value.contains("..") {
+                        parse_range(value)?
+                    } else {
+                        let left: usize = value.parse().map_err(invalid)?;
+                        (left, Some(left + 1))
+                    };
+                    group.log_col_left = Some(left);
+                    group.log_col_right = right;
+                    group.end = right.is_none();
+                },
+                "shift" => group.shift = Some(value.parse().map_err(invalid)?),
+                "comment" => group.comment = Some(value.to_owned()),
+                "sep" => group.sep = Some(value.to_owned()),
+                "missing" => group.missing = Some(value.to_owned()),
+                _ => return Err(format!("Unknown option {:?} of group. Options: hist, log, shift, comment, sep, missing", key))
+            }
+        }
+        Ok(group)
+    }
+}
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn job(json: &str) -> Job
+    {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn disabled(job: &Job, file: usize) -> Vec<usize>
+    {
+        job.files[file].log_cols
+            .iter()
+            .filter(|col| !col.is_enabled())
+            .map(|col| col.index)
+            .collect()
+    }
+
+    #[test]
+    fn exclude_interval_of_till_end()
+    {
+        let mut job = job(r#"{"out": "merged.out", "hist": "HistIsizeFast", "files": [
+            {"path": "data/a.dat", "log_cols": "3.."},
+            {"path": "data/b.dat", "log_cols": ["3..", {"index": 5, "trim_left": 2}]},
+            {"path": "other/c.dat", "log_cols": "3.."}
+        ]}"#);
+        let overrides = JobOverrides{
+            exclude_interval: vec!["data/*:26".to_owned(), "data/b.dat:5".to_owned(), "data/a.dat:2".to_owned()],
+            ..Default::default()
+        };
+        overrides.apply(&mut job);
+
+        assert_eq!(disabled(&job, 0), vec![26]);
+        assert_eq!(disabled(&job, 1), vec![5, 26]);
+        assert!(disabled(&job, 2).is_empty());
+        // columns left of "3.." are not part of the job
+        assert!(!job.files[0].log_cols.iter().any(|col| col.index == 2));
+        // the column with options is only disabled
+        assert_eq!(job.files[1].log_cols.iter().filter(|col| col.index == 5).count(), 1);
+    }
+
+    #[test]
+    fn exclude_file_and_fields()
+    {
+        let mut job = job(r#"{"out": "merged.out", "hist": "HistIsizeFast", "files": [
+            {"path": "data/a.dat", "log_cols": [3, 4]},
+            {"path": "data/b.dat", "log_cols": [3]}
+        ]}"#);
+        let overrides = JobOverrides{
+            out: Some("other.out".to_owned()),
+            bin_starting_point: Some(-1.5),
+            exclude_file: vec!["*/b.dat".to_owned()],
+            exclude_interval: vec!["data/a.dat:4".to_owned()],
+            ..Default::default()
+        };
+        overrides.apply(&mut job);
+
+        assert_eq!(job.out, "other.out");
+        assert_eq!(job.bin_starting_point, Some(-1.5));
+        assert!(job.files[0].is_enabled());
+        assert!(!job.files[1].is_enabled());
+        assert_eq!(disabled(&job, 0), vec![4]);
+    }
+}
@@ -0,0 +1,241 @@
+//! Guessing the layout of a data file for `create-job`, i.e.,
+//! the comment prefix, the separator, the histogram column and the log columns.
+//! Header names are preferred, otherwise the data is inspected
+
+use crate::read_input;
+
+/// Everything that could be guessed from a file
+#[derive(Debug, Clone, Default)]
+pub struct Detected
+{
+    pub comment: Option<String>,
+    /// None means whitespace
+    pub sep: Option<String>,
+    /// Number of header lines that do not start with a comment prefix
+    pub skip_header_lines: Option<usize>,
+    pub hist_col: Option<usize>,
+    pub log_col: Option<usize>,
+    /// The log columns start at `log_col` and go until the last column
+    pub till_end: bool,
+    /// Column names from the header, if there is one
+    pub names: Vec<String>
+}
+
+fn is_data_line(line: &str) -> bool
+{
+    line.split(|c: char| c == ',' || c.is_whitespace())
+        .find(|t| !t.is_empty())
+        .is_some_and(|t| t.parse::<f64>().is_ok())
+}
+
+/// Leading characters that can not be part of a number or a column name, e.g., "#" or "//"
+fn comment_prefix(line: &str) -> Option<String>
+{
+    let prefix: String = line.chars()
+        .take_while(|c| !c.is_alphanumeric() && !c.is_whitespace() && !matches!(c, '-' | '+' | '.'))
+        .collect();
+    if prefix.is_empty() {
+        None
+    } else {
+        Some(prefix)
+    }
+}
+
+fn split<'a>(line: &'a str, sep: &Option<String>) -> Vec<&'a str>
+{
+    match sep{
+        Some(sep) => line.split(sep.as_str()).map(str::trim).collect(),
+        None => line.split_whitespace().collect()
+    }
+}
+
+/// Integers that increase by exactly one from line to line
+fn is_consecutive(values: &[&str]) -> bool
+{
+    let ints: Option<Vec<i64>> = values.iter()
+        .map(|v| v.parse().ok())
+        .collect();
+    match ints{
+        Some(ints) if ints.len() > 1 => ints.windows(2).all(|w| w[1] - w[0] == 1),
+        _ => false
+    }
+}
+
+/// Floats that are not all integers
+fn is_float(values: &[&str]) -> bool
+{
+    let floats: Option<Vec<f64>> = values.iter()
+        .map(|v| v.parse().ok())
+        .collect();
+    match floats{
+        Some(floats) => floats.iter().any(|f| f.is_finite() && f.fract() != 0.0),
+        None => false
+    }
+}
+
+pub fn detect(path: &str) -> Detected
+{
+    detect_content(&read_input(path))
+}
+
+fn detect_content(content: &str) -> Detected
+{
+    let mut detected = Detected::default();
+
+    let lines: Vec<&str> = content.lines()
+        .map(str::trim)
+        .collect();
+    let first_data = match lines.iter().position(|l| is_data_line(l)){
+        Some(index) => index,
+        None => return detected
+    };
+
+    let header_lines: Vec<&str> = lines[..first_data]
+        .iter()
+        .copied()
+        .filter(|l| !l.is_empty())
+        .collect();
+    detected.comment = header_lines.first()
+        .and_then(|l| comment_prefix(l));
+    let all_commented = match &detected.comment{
+        Some(prefix) => header_lines.iter().all(|l| l.starts_with(prefix.as_str())),
+        None => header_lines.is_empty()
+    };
+    if !all_commented {
+        detected.skip_header_lines = Some(first_data);
+    }
+    let header = header_lines.first()
+        .map(|l| &l[detected.comment.as_ref().map_or(0, String::len)..]);
+
+    if lines[first_data].contains(',') {
+        detected.sep = Some(",".to_owned());
+    }
+
+    // only look at the data
+    let data: Vec<Vec<&str>> = lines[first_data..]
+        .iter()
+        .filter(|l| !l.is_empty() && !detected.comment.as_ref().is_some_and(|c| l.starts_with(c.as_str())))
+        .take(1000)
+        .map(|l| split(l, &detected.sep))
+        .collect();
+    let n_cols = data[0].len();
+    let column = |index: usize| -> Vec<&str> {
+        data.iter()
+            .filter_map(|row| row.get(index).copied())
+            .filter(|v| !v.eq_ignore_ascii_case("nan"))
+            .collect()
+    };
+
+    if let Some(header) = header {
+        detected.names = split(header, &detected.sep)
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+    }
+    let name = |index: usize| detected.names.get(index).map(|n| n.to_lowercase());
+
+    detected.hist_col = (0..n_cols)
+        .find(|&i| matches!(name(i).as_deref(), Some("bin" | "left" | "hist" | "bin_left" | "hist_left")) && is_consecutive(&column(i)))
+        .or_else(|| (0..n_cols).find(|&i| is_consecutive(&column(i))));
+
+    // right border of the bins, e.g. "left right"
+    let hist_right = detected.hist_col
+        .map(|h| h + 1)
+        .filter(|&r| r < n_cols && is_consecutive(&column(r)));
+    let is_hist = |i: usize| Some(i) == detected.hist_col || Some(i) == hist_right;
+
+    let intervals = (0..n_cols)
+        .find(|&i| name(i).is_some_and(|n| n.starts_with("interval")));
+    match intervals{
+        Some(index) => {
+            detected.log_col = Some(index);
+            detected.till_end = true;
+        },
+        None => {
+            detected.log_col = (0..n_cols)
+                .find(|&i| !is_hist(i) && name(i).is_some_and(|n| n.contains("log")))
+                .or_else(|| (0..n_cols).find(|&i| !is_hist(i) && is_float(&column(i))));
+        }
+    }
+    detected
+}
+
+impl Detected
+{
+    fn describe(&self, index: Option<usize>) -> String
+    {
+        match index{
+            None => "not detected".to_owned(),
+            Some(i) => match self.names.get(i){
+                Some(name) => format!("{} ({:?})", i, name),
+                None => i.to_string()
+            }
+        }
+    }
+
+    pub fn report(&self, path: &str)
+    {
+        eprintln!("Detected from {:?}:", path);
+        eprintln!("  comment: {:?}", self.comment);
+        match &self.sep{
+            Some(sep) => eprintln!("  separator: {:?}", sep),
+            None => eprintln!("  separator: whitespace")
+        }
+        if let Some(skip) = self.skip_header_lines {
+            eprintln!("  header lines without comment prefix: {}", skip);
+        }
+        eprintln!("  histogram column: {}", self.describe(self.hist_col));
+        let till_end = if self.till_end {" until the last column"} else {""};
+        eprintln!("  first log column: {}{}", self.describe(self.log_col), till_end);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn detect_rewl()
+    {
+        let content = "#left right log_10_prob interval_0 interval_1 interval_2\n\
+            #CMD: sir_exec rewl -n 3200\n\
+            0 1 -0.61 -0.61 NaN NaN\n\
+            1 2 -0.83 -0.83 NaN NaN\n\
+            2 3 -0.91 -0.91 -0.90 NaN\n";
+        let detected = detect_content(content);
+        assert_eq!(detected.comment.as_deref(), Some("#"));
+        assert_eq!(detected.sep, None);
+        assert_eq!(detected.skip_header_lines, None);
+        assert_eq!(detected.hist_col, Some(0));
+        assert_eq!(detected.log_col, Some(3));
+        assert!(detected.till_end);
+    }
+
+    #[test]
+    fn detect_simple_sample()
+    {
+        let content = "#M log_10_prob bin hits hits/sum_hits\n\
+            #sir_exec simple-sample --graph-type Sw 0.1 -n 3200\n\
+            0.0003125 2.89348353146332e0 1 2445304 2.44530791249266e-1\n\
+            0.000625 2.5857919489594425e0 2 1204041 1.2040429264686824e-1\n\
+            0.0009375 2.5385422575254215e0 3 1079920 1.0799217278747646e-1\n";
+        let detected = detect_content(content);
+        assert_eq!(detected.comment.as_deref(), Some("#"));
+        assert_eq!(detected.hist_col, Some(2));
+        assert_eq!(detected.log_col, Some(1));
+        assert!(!detected.till_end);
+    }
+
+    #[test]
+    fn detect_csv_without_comment()
+    {
+        let content = "bin,value\n0,-1.5\n1,-2.5\n2,-3.5\n";
+        let detected = detect_content(content);
+        assert_eq!(detected.comment, None);
+        assert_eq!(detected.sep.as_deref(), Some(","));
+        assert_eq!(detected.skip_header_lines, Some(1));
+        assert_eq!(detected.hist_col, Some(0));
+        assert_eq!(detected.log_col, Some(1));
+    }
+}
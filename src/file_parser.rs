@@ -1,21 +1,122 @@
-use std::{io::{BufReader, BufRead, Read}, str::FromStr};
+use std::str::FromStr;
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 use core::ops::Deref;
-use std::fs::File;
 use sampling::{HistIsizeFast, Histogram};
 use core::fmt::Debug;
-use crate::{LogColRange};
+use crate::{LogColRange, hdf5_reader, checkpoint, read_input, log_cols::{deserialize_log_cols, LogColsSchema}};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct FileInfo{
     pub path: String,
     pub index_hist_left: Option<usize>,
     pub index_hist_right: Option<usize>,
+    /// Can also be given in compact form, e.g., `["3..27", {"index": 5, "trim_left": 2}]` or `"3.."`
+    #[serde(deserialize_with = "deserialize_log_cols")]
+    #[schemars(with = "LogColsSchema")]
     pub log_cols: Vec<LogCol>,
-    pub comment: Option<String>,
+    pub comment: Option<Comment>,
     pub sep: Option<String>,
     pub shift: Option<isize>,
-    pub missing: Option<String>
+    pub missing: Option<String>,
+    /// If true, everything after a comment prefix is ignored, 
+    /// even if the line contains data before the comment
+    pub inline_comments: Option<bool>,
+    /// Number of lines at the start of the file that are skipped,
+    /// regardless of their content
+    pub skip_header_lines: Option<usize>,
+    /// Bins smaller than this are removed from all intervals of this file.
+    /// Refers to the bins after the `shift` was applied
+    pub min_bin: Option<isize>,
+    /// Bins larger than this are removed from all intervals of this file.
+    /// Refers to the bins after the `shift` was applied
+    pub max_bin: Option<isize>,
+    /// Automatic trimming of the edges of all intervals of this file
+    pub auto_trim: Option<AutoTrim>,
+    /// Use all intervals of this file as reference for the normalization, 
+    /// see `Normalization::Reference`
+    pub reference: Option<bool>,
+    /// How to read the file. If omitted, the file is read as text
+    pub format: Option<FileFormat>,
+    /// If false, the file is not merged. Default: true
+    pub enabled: Option<bool>,
+    /// Free text, e.g., why the file is disabled. Not used by the merger
+    pub note: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub enum FileFormat
+{
+    /// Text file, columns are separated by `sep`
+    Text,
+    /// Read the columns from datasets of an HDF5 file.
+    /// Requires the feature `hdf5`
+    Hdf5(Hdf5Info),
+    /// Json serialized WangLandau or Rewl state of the `sampling` crate.
+    /// The `log_cols` are the indices of the walkers you want to use,
+    /// the histogram is taken from each walker
+    SamplingJson
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Hdf5Info
+{
+    /// Name of the dataset containing the logarithmic probabilities.
+    /// Has to be 1D or 2D. The `log_cols` index the columns of a 2D dataset,
+    /// for a 1D dataset use index 0
+    pub log_dataset: String,
+    /// Name of the dataset containing the histogram bins. 
+    /// For a 2D dataset `index_hist_left` and `index_hist_right` are used as columns.
+    /// If omitted, the row number will be used as bin
+    pub hist_dataset: Option<String>
+}
+
+/// Prefix(es) marking a comment. Leading whitespace of a line is ignored
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum Comment
+{
+    Single(String),
+    Multiple(Vec<String>)
+}
+
+impl Comment
+{
+    fn prefixes(&self) -> &[String]
+    {
+        match self{
+            Self::Single(c) => std::slice::from_ref(c),
+            Self::Multiple(c) => c
+        }
+    }
+
+    /// Is the (already left trimmed) line a comment?
+    pub fn is_comment(&self, line: &str) -> bool
+    {
+        self.prefixes()
+            .iter()
+            .any(|c| line.starts_with(c.as_str()))
+    }
+
+    /// Removes everything starting from the first comment prefix
+    pub fn strip_inline<'a>(&self, line: &'a str) -> &'a str
+    {
+        let end = self.prefixes()
+            .iter()
+            .filter_map(|c| line.find(c.as_str()))
+            .min()
+            .unwrap_or(line.len());
+        &line[..end]
+    }
+}
+
+impl From<String> for Comment
+{
+    fn from(c: String) -> Self {
+        Self::Single(c)
+    }
 }
 
 pub enum LeftRight{
@@ -48,6 +149,37 @@ fn get_iter<'a>(sep: &'a Option<String>, line: &'a str) -> Box<dyn Iterator<Item
 
 impl FileInfo{
 
+    /// Iterates over all lines that contain data, i.e., the header lines, 
+    /// comments and blank lines are skipped.
+    /// `global_comment` is only used if the file does not specify its own comment
+    fn data_lines<'a>(&'a self, content: &'a str, global_comment: Option<&'a Comment>) -> impl Iterator<Item=&'a str> + 'a
+    {
+        let comment = self.comment.as_ref().or(global_comment);
+        let inline = self.inline_comments.unwrap_or(false);
+        content.lines()
+            .skip(self.skip_header_lines.unwrap_or(0))
+            .filter_map(
+                move |line|
+                {
+                    let mut line = line.trim_start();
+                    if let Some(c) = comment {
+                        if c.is_comment(line){
+                            return None;
+                        }
+                        if inline {
+                            line = c.strip_inline(line);
+                        }
+                    }
+                    let line = line.trim_end();
+                    if line.is_empty(){
+                        None
+                    } else {
+                        Some(line)
+                    }
+                }
+            )
+    }
+
     fn collect_vals<'a, I, I2, T>(&self, mut iter: I, sorted_index_iter: I2, target: &mut [Vec<T>])
     where I: Iterator<Item=&'a str>,
         I2: Iterator<Item=usize>,
@@ -108,69 +240,61 @@ impl FileInfo{
             )
     }
 
-    fn count_cols(&self, global_comment: &Option<String>) -> usize
+    fn count_cols(&self, content: &str, global_comment: Option<&Comment>) -> usize
     {
-        let file = File::open(&self.path)
-            .expect("Unable to open file");
-        let buf = BufReader::new(file);
-
-        for line in buf.lines()
+        let first = self.data_lines(content, global_comment).next();
+        match first
         {
-            let string = line.unwrap();
-            if let Some(c) = &self.comment{
-                if string.starts_with(c){
-                    continue;
-                }
-            } else if let Some(c) = global_comment {
-                if string.starts_with(c){
-                    continue;
-                }
-            }
-            let iter = get_iter(&self.sep, &string);
-            return iter.count();
+            Some(line) => get_iter(&self.sep, line).count(),
+            None => 0
         }
-        
-        0
     }
 
-    pub fn log_cols_till_end(&mut self, global_comment: &Option<String>)
+    fn has_till_end(&self) -> bool
     {
-        let count = self.count_cols(global_comment);
-        assert_eq!(self.log_cols.len(), 1);
-        let next_index = self.log_cols[0].index + 1;
+        self.log_cols.iter().any(|col| col.till_end.unwrap_or(false))
+    }
 
-        self.log_cols
-            .extend(
-                (next_index..count)
-                    .map(LogCol::new)
-            );
+    /// Adds the columns following each column with `till_end`, until the last column of the file.
+    /// Columns that are already present are not changed.
+    /// Reads the file, which is otherwise only done when the intervals are read,
+    /// see [FileInfo::get_intervals]
+    pub fn log_cols_till_end(&mut self, global_comment: &Option<Comment>)
+    {
+        if !self.is_enabled() || !self.has_till_end() {
+            return;
+        }
+        let content = read_input(&self.path);
+        self.expand_till_end(&content, global_comment.as_ref());
     }
 
-    fn count_lines<T>(&self, reader: BufReader<T>) -> isize
-    where T: Read
+    fn expand_till_end(&mut self, content: &str, global_comment: Option<&Comment>)
     {
-        let mut counter: isize = 0;
-        for line in reader.lines()
+        if !matches!(self.format, None | Some(FileFormat::Text)) {
+            panic!("'till_end' (or \"N..\") is only supported for text files - {:?}", self);
+        }
+        let count = self.count_cols(content, global_comment);
+
+        let mut new_cols = Vec::new();
+        for col in self.log_cols.iter_mut().filter(|col| col.till_end.unwrap_or(false))
         {
-            let s = match line {
-                Ok(s) => s,
-                Err(e) => {
-                    panic!("Error in {:?} while reading file. Error is {:?}", self, e)
-                }
-            };
-            match &self.comment
-            {
-                Some(c) => {
-                    if !s.starts_with(c)
-                    {
-                        counter +=1;
-                    }
-                },
-                None => counter +=1
+            col.till_end = None;
+            new_cols.extend(
+                (col.index + 1..count)
+                    .map(LogCol::new)
+            );
+        }
+        for col in new_cols
+        {
+            if !self.log_cols.iter().any(|c| c.index == col.index) {
+                self.log_cols.push(col);
             }
-            
         }
-        counter
+    }
+
+    fn count_lines(&self, content: &str) -> isize
+    {
+        self.data_lines(content, None).count() as isize
     }
 
     pub fn sort_cols(&mut self)
@@ -187,30 +311,29 @@ impl FileInfo{
         }
     }
 
-    pub fn get_log_prob(&self) -> Vec<Vec<f64>>
+    /// Reads the file into memory. Only text files are read here,
+    /// for every other format an empty String is returned
+    pub fn read_content(&self) -> String
     {
-        let file = File::open(&self.path)
-            .expect("unable to open file");
-        let buf_reader = BufReader::new(file);
-
-        let mut log_probs: Vec<Vec<f64>> = vec![Vec::new(); self.log_cols.len()];
+        match &self.format{
+            None | Some(FileFormat::Text) => read_input(&self.path),
+            _ => String::new()
+        }
+    }
 
-        for line in buf_reader.lines()
-        {
-            let line = line.unwrap();
-            if let Some(pattern) = &self.comment
-            {
-                if line.starts_with(pattern){
-                    continue;
-                }
+    /// `content` is only used for text files, see [FileInfo::read_content]
+    pub fn get_log_prob(&self, content: &str) -> Vec<Vec<f64>>
+    {
+        let mut log_probs = match &self.format{
+            None | Some(FileFormat::Text) => self.get_log_prob_text(content),
+            Some(FileFormat::Hdf5(info)) => {
+                let iter = self.log_cols.iter().map(|e| e.index);
+                hdf5_reader::read_columns(&self.path, &info.log_dataset, iter)
+            },
+            Some(FileFormat::SamplingJson) => {
+                checkpoint::read_walkers(self).1
             }
-            let iter = self.log_cols.iter().map(|e| e.index);
-
-            match &self.sep{
-                Some(sep) => self.collect_floats(line.split(sep), iter, &mut log_probs),
-                None => self.collect_floats(line.split_whitespace(), iter, &mut log_probs)
-            };
-        }
+        };
 
         log_probs.iter_mut()
             .for_each(
@@ -232,18 +355,118 @@ impl FileInfo{
         log_probs
     }
 
-    pub fn get_hist_fast(&self) -> HistIsizeFast
+    fn get_log_prob_text(&self, content: &str) -> Vec<Vec<f64>>
+    {
+        let iter = self.log_cols.iter().map(|e| e.index);
+        self.get_columns_text(content, iter, self.log_cols.len())
+    }
+
+    fn get_columns_text<I>(&self, content: &str, sorted_index_iter: I, len: usize) -> Vec<Vec<f64>>
+    where I: Iterator<Item=usize> + Clone
+    {
+        let mut columns: Vec<Vec<f64>> = vec![Vec::new(); len];
+
+        for line in self.data_lines(content, None)
+        {
+            let iter = sorted_index_iter.clone();
+
+            match &self.sep{
+                Some(sep) => self.collect_floats(line.split(sep), iter, &mut columns),
+                None => self.collect_floats(line.split_whitespace(), iter, &mut columns)
+            };
+        }
+
+        columns
+    }
+
+    fn auto_trim_of<'a>(&'a self, col: &'a LogCol) -> Option<&'a AutoTrim>
+    {
+        col.auto_trim
+            .as_ref()
+            .or(self.auto_trim.as_ref())
+    }
+
+    /// Reads the hit columns required for the automatic trimming.
+    /// Returns the hits for each of the `log_cols`, None if they do not use hits
+    fn get_hits(&self, content: &str) -> Vec<Option<Vec<f64>>>
+    {
+        let mut hit_indices: Vec<usize> = self.log_cols
+            .iter()
+            .filter_map(|col| self.auto_trim_of(col).and_then(|a| a.hits_col))
+            .collect();
+        hit_indices.sort_unstable();
+        hit_indices.dedup();
+
+        if hit_indices.is_empty(){
+            return vec![None; self.log_cols.len()];
+        }
+
+        let iter = hit_indices.iter().copied();
+        let columns = match &self.format{
+            None | Some(FileFormat::Text) => self.get_columns_text(content, iter, hit_indices.len()),
+            Some(FileFormat::Hdf5(info)) => hdf5_reader::read_columns(&self.path, &info.log_dataset, iter),
+            Some(FileFormat::SamplingJson) => panic!("hits_col is not supported for checkpoints {:?}", self)
+        };
+
+        self.log_cols
+            .iter()
+            .map(
+                |col|
+                {
+                    self.auto_trim_of(col)
+                        .and_then(|a| a.hits_col)
+                        .map(
+                            |hits_col|
+                            {
+                                let pos = hit_indices.binary_search(&hits_col).unwrap();
+                                columns[pos].clone()
+                            }
+                        )
+                }
+            ).collect()
+    }
+
+    fn get_hist_bins_text<I>(&self, content: &str, sorted_index_iter: I, len: usize) -> Vec<Vec<isize>>
+    where I: Iterator<Item=usize> + Clone
     {
-        let file = File::open(&self.path)
-            .expect("unable to open file");
-        let buf_reader = BufReader::new(file);
+        let mut hist_bins: Vec<Vec<isize>> = vec![Vec::new(); len];
+
+        for line in self.data_lines(content, None)
+        {
+
+            let iter = sorted_index_iter.clone();
+
+            match &self.sep{
+                Some(sep) => self.collect_vals(line.split(sep), iter, &mut hist_bins),
+                None => self.collect_vals(line.split_whitespace(), iter, &mut hist_bins)
+            };
+        }
+        hist_bins
+    }
 
+    /// `content` is only used for text files, see [FileInfo::read_content]
+    pub fn get_hist_fast(&self, content: &str) -> HistIsizeFast
+    {
         let shift = self.shift.unwrap_or(0);
 
-        let index_hist_left = match self.index_hist_left {
-            Some(v) => v,
-            None => {
-                let lines = self.count_lines(buf_reader);
+        let hdf5_info = match &self.format{
+            Some(FileFormat::Hdf5(info)) => Some(info),
+            Some(FileFormat::SamplingJson) => {
+                panic!("Every walker has its own histogram, there is no common histogram in {:?}", self)
+            },
+            _ => None
+        };
+
+        let index_hist_left = match (self.index_hist_left, hdf5_info) {
+            (_, Some(Hdf5Info{hist_dataset: None, log_dataset})) => {
+                let rows = hdf5_reader::count_rows(&self.path, log_dataset) as isize;
+                return HistIsizeFast::new(shift, rows + shift)
+                    .expect("unable to create histogram");
+            },
+            (Some(v), _) => v,
+            (None, Some(_)) => 0,
+            (None, None) => {
+                let lines = self.count_lines(content);
                 return HistIsizeFast::new(shift, lines + shift)
                     .expect("unable to create histogram");
             }
@@ -265,26 +488,14 @@ impl FileInfo{
 
         hist_indizes.sort_unstable_by_key(|e| e.index);
 
-        let mut hist_bins: Vec<Vec<isize>> = vec![Vec::new(); hist_indizes.len()];
-
-
-        for line in buf_reader.lines()
-        {
-            let line = line.unwrap();
-            if let Some(pattern) = &self.comment
-            {
-                if line.starts_with(pattern){
-                    continue;
-                }
-            }
-
-            let iter = hist_indizes.iter().map(|e| e.index);
-
-            match &self.sep{
-                Some(sep) => self.collect_vals(line.split(sep), iter, &mut hist_bins),
-                None => self.collect_vals(line.split_whitespace(), iter, &mut hist_bins)
-            };
-        }
+        let iter = hist_indizes.iter().map(|e| e.index);
+        let hist_bins = match hdf5_info {
+            Some(info) => {
+                let dataset = info.hist_dataset.as_ref().unwrap();
+                hdf5_reader::read_bin_columns(&self.path, dataset, iter)
+            },
+            None => self.get_hist_bins_text(content, iter, hist_indizes.len())
+        };
 
         hist_bins.iter()
             .for_each(
@@ -335,73 +546,446 @@ impl FileInfo{
 
     pub fn get_logs_and_hists(&self) -> (Vec<HistIsizeFast>, Vec<Vec<f64>>)
     {
-        let mut logs = self.get_log_prob();
-        let e_hist = self.get_hist_fast();
+        self.get_intervals()
+            .into_iter()
+            .map(|interval| (interval.hist, interval.log_prob))
+            .unzip()
+    }
+
+    pub fn is_enabled(&self) -> bool
+    {
+        self.enabled.unwrap_or(true)
+    }
 
-        let hists: Vec<_> = logs.iter_mut()
+    /// Reads, trims and masks all intervals of this file
+    pub fn get_intervals(&self) -> Vec<Interval>
+    {
+        self.get_intervals_carrying(None).0
+    }
+
+    /// Like [FileInfo::get_intervals], but also returns all lines of a text file 
+    /// starting with `carry_prefix`, e.g., "#CMD:". The file is only read once
+    pub fn get_intervals_carrying(&self, carry_prefix: Option<&str>) -> (Vec<Interval>, Vec<String>)
+    {
+        if !self.is_enabled() || !(self.has_till_end() || self.log_cols.iter().any(LogCol::is_enabled)) {
+            return (Vec::new(), Vec::new());
+        }
+
+        let content = self.read_content();
+        let carried = match carry_prefix{
+            Some(prefix) => {
+                content.lines()
+                    .filter(|line| line.trim_start().starts_with(prefix))
+                    .map(|line| line.trim().to_owned())
+                    .collect()
+            },
+            None => Vec::new()
+        };
+
+        let intervals = if self.has_till_end() || self.log_cols.iter().any(|col| !col.is_enabled()) {
+            let mut file = self.clone();
+            if file.has_till_end() {
+                // the number of columns is only known now, as the file can only be read once
+                file.expand_till_end(&content, None);
+                file.sort_cols();
+            }
+            file.log_cols.retain(LogCol::is_enabled);
+            if file.log_cols.is_empty() {
+                Vec::new()
+            } else {
+                file.intervals_of(&content)
+            }
+        } else {
+            self.intervals_of(&content)
+        };
+        (intervals, carried)
+    }
+
+    /// `content` is only used for text files, see [FileInfo::read_content]
+    fn intervals_of(&self, content: &str) -> Vec<Interval>
+    {
+        let (e_hists, logs) = match &self.format{
+            Some(FileFormat::SamplingJson) => checkpoint::read_walkers(self),
+            _ => {
+                let logs = self.get_log_prob(content);
+                let e_hist = self.get_hist_fast(content);
+                (vec![e_hist; logs.len()], logs)
+            }
+        };
+        let hits = self.get_hits(content);
+
+        logs.into_iter()
             .zip(self.log_cols.iter())
+            .zip(e_hists.iter())
+            .zip(hits.iter())
+            .flat_map(
+                |(((log_vec, col), e_hist), hits)|
+                {
+                    self.trim_col(log_vec, col, e_hist, hits.as_deref())
+                }
+            ).collect()
+    }
+
+    /// Masks, trims and removes the NaNs from the interval of one column.
+    /// Depending on the gap policy the result can consist of multiple intervals
+    fn trim_col(&self, mut log_vec: Vec<f64>, col: &LogCol, e_hist: &HistIsizeFast, hits: Option<&[f64]>) -> Vec<Interval>
+    {
+        let bins: Vec<isize> = e_hist.bin_iter().collect();
+
+        if let Some(mask) = &col.mask_bins{
+            log_vec.iter_mut()
+                .zip(bins.iter())
+                .filter(|(_, bin)| mask.contains(bin))
+                .for_each(|(val, _)| *val = f64::NAN);
+        }
+
+        if !log_vec.iter().any(|v| v.is_finite()){
+            panic!("Only Invalid entries in {:?} in {:?}", col, self)
+        }
+        
+        let (mut index_left, mut index_right) = strip_nan_edges(&log_vec, 0, log_vec.len() - 1)
+            .unwrap();
+
+        if let Some(min_bin) = col.min_bin.or(self.min_bin){
+            while index_left < index_right && bins[index_left] < min_bin {
+                index_left += 1;
+            }
+        }
+
+        if let Some(max_bin) = col.max_bin.or(self.max_bin){
+            while index_right > index_left && bins[index_right] > max_bin {
+                index_right -= 1;
+            }
+        }
+
+        if bins[index_left] < col.min_bin.or(self.min_bin).unwrap_or(isize::MIN)
+            || bins[index_right] > col.max_bin.or(self.max_bin).unwrap_or(isize::MAX)
+        {
+            panic!("No bins left after applying min_bin and max_bin to {:?} in {:?}", col, self)
+        }
+
+        if let Some(auto) = self.auto_trim_of(col){
+            let old_left = index_left;
+            let old_right = index_right;
+            auto.trim(&log_vec, hits, &mut index_left, &mut index_right);
+            let range = |trimmed: &[isize]| {
+                match (trimmed.first(), trimmed.last()){
+                    (Some(first), Some(last)) => format!("{} bins [{}, {}]", trimmed.len(), first, last),
+                    _ => "nothing".to_owned()
+                }
+            };
+            eprintln!(
+                "Auto trim of column {} in {:?}: trimmed {} from the left and {} from the right",
+                col.index,
+                self.path,
+                range(&bins[old_left..index_left]),
+                range(&bins[index_right+1..=old_right])
+            );
+        }
+
+        if let Some(trim_left) = col.trim_left{
+            index_left += trim_left;
+            if index_left >= index_right {
+                eprintln!("Did you trim to much? {:?} {:?}", col, self);
+            }
+        }
+
+        if let Some(trim_right) = col.trim_right{
+            index_right -= trim_right;
+            if index_left >= index_right {
+                eprintln!("Did you trim to much? {:?} {:?}", col, self);
+            }
+        }
+
+        // the cuts can end on NaNs
+        let (index_left, index_right) = match strip_nan_edges(&log_vec, index_left, index_right){
+            Some(indices) => indices,
+            None => panic!("Only Invalid entries left after trimming {:?} in {:?}", col, self)
+        };
+
+        // remove NaNs and trim interval
+        let mut log_vec = log_vec[index_left..=index_right].to_vec();
+        let bins = &bins[index_left..=index_right];
+
+        let has_gaps = log_vec.iter().any(|v| v.is_nan());
+        let segments = match col.gaps.unwrap_or_default(){
+            _ if !has_gaps => vec![(0, log_vec.len())],
+            GapPolicy::Keep => vec![(0, log_vec.len())],
+            GapPolicy::Reject => {
+                let missing: Vec<_> = log_vec.iter()
+                    .zip(bins.iter())
+                    .filter(|(val, _)| val.is_nan())
+                    .map(|(_, bin)| bin)
+                    .collect();
+                panic!("Interval contains NaNs in the bins {:?} - {:?} {:?}", missing, col, self)
+            },
+            GapPolicy::Interpolate => {
+                interpolate_gaps(&mut log_vec);
+                vec![(0, log_vec.len())]
+            },
+            GapPolicy::Split => {
+                let segments = split_at_gaps(&log_vec);
+                if segments.is_empty() {
+                    panic!("Only Invalid entries after splitting {:?} in {:?} - every part has only one bin", col, self)
+                }
+                eprintln!(
+                    "Splitting column {} of {:?} into {} intervals, because it contains NaNs",
+                    col.index,
+                    self.path,
+                    segments.len()
+                );
+                segments
+            }
+        };
+
+        segments.into_iter()
             .map(
-                |(log_vec, col)|
+                |(start, end)|
                 {
-                    if !log_vec.iter().any(|v| v.is_finite()){
-                        panic!("Only Invalid entries in {:?} in {:?}", col, self)
-                    }
-                    
-                    let mut index_left = 0;
-                    for (index, val) in log_vec.iter().enumerate()
-                    {
-                        if !val.is_nan(){
-                            index_left = index;
-                            break;
+                    let left = bins[start];
+                    let right = bins[end - 1];
+                    let hist = HistIsizeFast::new_inclusive(left, right).unwrap();
+                    let log_prob = log_vec[start..end].to_vec();
+                    assert_eq!(hist.bin_count(), log_prob.len(), "Lenght of Hist does not match length of log_vec");
+                    Interval{
+                        hist,
+                        log_prob,
+                        info: IntervalInfo{
+                            path: self.path.clone(),
+                            column: col.index,
+                            left,
+                            right,
+                            reference: col.reference.or(self.reference).unwrap_or(false)
                         }
                     }
+                }
+            ).collect()
+    }
+}
 
-                    let mut index_right = log_vec.len() - 1;
-                    for (index, val) in log_vec.iter().enumerate().rev()
-                    {
-                        if !val.is_nan(){
-                            index_right = index;
-                            break;
-                        }
-                    }
+/// Where an interval comes from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IntervalInfo
+{
+    pub path: String,
+    /// Index of the log column
+    pub column: usize,
+    /// Left most bin of the interval (after trimming), inclusive
+    pub left: isize,
+    /// Right most bin of the interval (after trimming), inclusive
+    pub right: isize,
+    /// Is this interval used as reference for the normalization?
+    pub reference: bool
+}
 
-                    if let Some(trim_left) = col.trim_left{
-                        index_left += trim_left;
-                        if index_left >= index_right {
-                            eprintln!("Did you trim to much? {:?} {:?}", col, self);
-                        }
-                    }
+/// An interval that is ready to be merged
+#[derive(Debug, Clone)]
+pub struct Interval
+{
+    pub hist: HistIsizeFast,
+    pub log_prob: Vec<f64>,
+    pub info: IntervalInfo
+}
 
-                    if let Some(trim_right) = col.trim_right{
-                        index_right -= trim_right;
-                        if index_left >= index_right {
-                            eprintln!("Did you trim to much? {:?} {:?}", col, self);
-                        }
-                    }
+impl Interval
+{
+    /// Interval from an in-memory logarithmic probability (base 10), 
+    /// where `log_prob[0]` corresponds to the bin `left`.
+    /// Non finite entries at the edges, e.g., NaN or -inf of unvisited bins, are removed.
+    /// `name` is used in place of the path in the provenance
+    pub fn new(left: isize, log_prob: Vec<f64>, name: String) -> Self
+    {
+        let start = log_prob.iter().position(|v| v.is_finite());
+        let end = log_prob.iter().rposition(|v| v.is_finite());
+        let (start, end) = match (start, end){
+            (Some(start), Some(end)) => (start, end),
+            _ => panic!("Interval {:?} contains no finite values", name)
+        };
+        let log_prob = log_prob[start..=end].to_vec();
+        let left = left + start as isize;
+        if log_prob.iter().any(|v| !v.is_finite()) {
+            panic!("Interval {:?} contains non finite values between its finite edges", name)
+        }
+        let right = left + log_prob.len() as isize - 1;
+        let hist = HistIsizeFast::new_inclusive(left, right)
+            .expect("unable to create histogram");
+        Self{
+            hist,
+            log_prob,
+            info: IntervalInfo{
+                path: name,
+                column: 0,
+                left,
+                right,
+                reference: false
+            }
+        }
+    }
+}
 
-                    // remove NaNs and trim interval
-                    *log_vec = log_vec[index_left..=index_right].to_vec();
-                    
-                    let mut iter = e_hist.bin_iter();
+/// Moves `left` and `right` (inclusive) inwards until both point to entries that are not NaN.
+/// None if there are no such entries in between
+fn strip_nan_edges(log_vec: &[f64], left: usize, right: usize) -> Option<(usize, usize)>
+{
+    if left > right || right >= log_vec.len() {
+        return None;
+    }
+    let left = (left..=right).find(|&i| !log_vec[i].is_nan())?;
+    let right = (left..=right).rev().find(|&i| !log_vec[i].is_nan())?;
+    Some((left, right))
+}
 
-                    let left = iter.nth(index_left).unwrap();
-                    let diff = index_right - index_left - 1;
-                    let right = iter.nth(diff).unwrap();
-                    let hist = HistIsizeFast::new_inclusive(left, right).unwrap();
-                    
-                    assert_eq!(hist.bin_count(), log_vec.len(), "Lenght of Hist does not match length of log_vec");
-                    hist
+/// Linear interpolation of the NaNs. The first and last entry have to be finite
+fn interpolate_gaps(log_vec: &mut [f64])
+{
+    let mut last_valid = 0;
+    for index in 1..log_vec.len()
+    {
+        if log_vec[index].is_nan(){
+            continue;
+        }
+        let gap = index - last_valid;
+        if gap > 1 {
+            let left = log_vec[last_valid];
+            let slope = (log_vec[index] - left) / gap as f64;
+            for (offset, val) in log_vec[last_valid+1..index].iter_mut().enumerate()
+            {
+                *val = left + slope * (offset + 1) as f64;
+            }
+        }
+        last_valid = index;
+    }
+}
+
+/// Returns the ranges (start, end exclusive) of the parts of the interval that do not contain NaNs.
+/// Parts with only one bin are dropped, as they cannot be glued
+fn split_at_gaps(log_vec: &[f64]) -> Vec<(usize, usize)>
+{
+    let mut segments = Vec::new();
+    let mut start = None;
+    for (index, val) in log_vec.iter().enumerate()
+    {
+        match (val.is_nan(), start){
+            (false, None) => start = Some(index),
+            (true, Some(s)) => {
+                segments.push((s, index));
+                start = None;
+            },
+            _ => ()
+        }
+    }
+    if let Some(s) = start {
+        segments.push((s, log_vec.len()));
+    }
+    segments.retain(|(start, end)| end - start > 1);
+    segments
+}
+
+/// Automatically trims edge bins that did not converge well
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AutoTrim
+{
+    /// Edge bins are trimmed while the derivative of the logarithmic probability 
+    /// differs from the mean derivative of its neighbors by more than this
+    pub derivative_threshold: Option<f64>,
+    /// Number of neighboring derivatives the derivative at the edge is compared to. Default: 5
+    pub window: Option<usize>,
+    /// Index of the column containing the number of hits of each bin
+    pub hits_col: Option<usize>,
+    /// Edge bins with less hits than this are trimmed. Default: 1
+    pub min_hits: Option<f64>
+}
+
+impl AutoTrim
+{
+    /// Moves `index_left` and `index_right` inwards until the edge bins are fine
+    fn trim(&self, log_vec: &[f64], hits: Option<&[f64]>, index_left: &mut usize, index_right: &mut usize)
+    {
+        if let Some(hits) = hits {
+            let min_hits = self.min_hits.unwrap_or(1.0);
+            let too_few = |h: f64| h.is_nan() || h < min_hits;
+            while *index_left < *index_right && too_few(hits[*index_left]) {
+                *index_left += 1;
+            }
+            while *index_right > *index_left && too_few(hits[*index_right]) {
+                *index_right -= 1;
+            }
+        }
+
+        if let Some(threshold) = self.derivative_threshold {
+            let window = self.window.unwrap_or(5).max(1);
+            let derivative = |i: usize| log_vec[i + 1] - log_vec[i];
+            let mean = |range: std::ops::Range<usize>| {
+                let len = range.len() as f64;
+                range.map(derivative).sum::<f64>() / len
+            };
+
+            while *index_left + window < *index_right
+            {
+                let edge = derivative(*index_left);
+                let neighbors = mean(*index_left + 1..*index_left + 1 + window);
+                if (edge - neighbors).abs() > threshold {
+                    *index_left += 1;
+                } else {
+                    break;
+                }
+            }
+
+            while *index_right > *index_left + window
+            {
+                let edge = derivative(*index_right - 1);
+                let neighbors = mean(*index_right - 1 - window..*index_right - 1);
+                if (edge - neighbors).abs() > threshold {
+                    *index_right -= 1;
+                } else {
+                    break;
                 }
-            ).collect();
-        (hists, logs)
+            }
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// What to do with NaNs inside of an interval, i.e., after the leading and trailing NaNs were removed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, JsonSchema)]
+pub enum GapPolicy
+{
+    /// Keep the NaNs
+    #[default]
+    Keep,
+    /// Split the interval into multiple intervals that do not contain NaNs
+    Split,
+    /// Linear interpolation of the logarithmic probability
+    Interpolate,
+    /// Abort the merge
+    Reject
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct LogCol{
     pub index: usize,
     pub trim_right: Option<usize>,
-    pub trim_left: Option<usize>
+    pub trim_left: Option<usize>,
+    /// Overwrites `min_bin` of the file for this column
+    pub min_bin: Option<isize>,
+    /// Overwrites `max_bin` of the file for this column
+    pub max_bin: Option<isize>,
+    /// What to do with NaNs inside the interval. Default: Keep
+    pub gaps: Option<GapPolicy>,
+    /// These bins are set to NaN. Refers to the bins after the `shift` was applied
+    pub mask_bins: Option<Vec<isize>>,
+    /// Overwrites `auto_trim` of the file for this column
+    pub auto_trim: Option<AutoTrim>,
+    /// Overwrites `reference` of the file for this column
+    pub reference: Option<bool>,
+    /// Also use all following columns until the last column of the file (without any options),
+    /// unless they are specified explicitly
+    pub till_end: Option<bool>,
+    /// If false, this column is not merged. Default: true
+    pub enabled: Option<bool>,
+    /// Free text, e.g., why the column is disabled. Not used by the merger
+    pub note: Option<String>
 }
 
 impl LogCol{
@@ -410,9 +994,23 @@ impl LogCol{
         Self{
             index,
             trim_left: None,
-            trim_right: None
+            trim_right: None,
+            min_bin: None,
+            max_bin: None,
+            gaps: None,
+            mask_bins: None,
+            auto_trim: None,
+            reference: None,
+            till_end: None,
+            enabled: None,
+            note: None
         }
     }
+
+    pub fn is_enabled(&self) -> bool
+    {
+        self.enabled.unwrap_or(true)
+    }
 }
 
 impl Deref for LogCol
@@ -432,7 +1030,16 @@ pub fn print_log_col_range(opt: LogColRange)
                 LogCol{
                     index,
                     trim_left: opt.trim_left,
-                    trim_right: opt.trim_right
+                    trim_right: opt.trim_right,
+                    min_bin: opt.min_bin,
+                    max_bin: opt.max_bin,
+                    gaps: None,
+                    mask_bins: None,
+                    auto_trim: None,
+                    reference: None,
+                    till_end: None,
+                    enabled: None,
+                    note: None
                 }
             }
         ).collect();
@@ -440,3 +1047,45 @@ pub fn print_log_col_range(opt: LogColRange)
     serde_json::to_writer_pretty(std::io::stdout(), &v).unwrap()
 }
 
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const NAN: f64 = f64::NAN;
+
+    #[test]
+    fn strip_nan_edges_after_cuts()
+    {
+        let log_vec = [NAN, -1.0, NAN, -3.0, -4.0, NAN];
+        assert_eq!(strip_nan_edges(&log_vec, 0, 5), Some((1, 4)));
+        // e.g. min_bin landing on a NaN
+        assert_eq!(strip_nan_edges(&log_vec, 2, 4), Some((3, 4)));
+        assert_eq!(strip_nan_edges(&log_vec, 2, 2), None);
+        assert_eq!(strip_nan_edges(&log_vec, 4, 3), None);
+        assert_eq!(strip_nan_edges(&[NAN, NAN], 0, 1), None);
+    }
+
+    #[test]
+    fn interpolate_gaps_linear()
+    {
+        let mut log_vec = [-1.0, NAN, NAN, -4.0, NAN, -2.0];
+        interpolate_gaps(&mut log_vec);
+        assert_eq!(log_vec, [-1.0, -2.0, -3.0, -4.0, -3.0, -2.0]);
+
+        let mut no_gaps = [-1.0, -2.0];
+        interpolate_gaps(&mut no_gaps);
+        assert_eq!(no_gaps, [-1.0, -2.0]);
+    }
+
+    #[test]
+    fn split_at_gaps_drops_single_bins()
+    {
+        let log_vec = [-1.0, -2.0, NAN, -3.0, NAN, NAN, -4.0, -5.0, -6.0];
+        assert_eq!(split_at_gaps(&log_vec), vec![(0, 2), (6, 9)]);
+        assert_eq!(split_at_gaps(&[-1.0, -2.0]), vec![(0, 2)]);
+        assert!(split_at_gaps(&[-1.0, NAN, -2.0]).is_empty());
+        assert!(split_at_gaps(&[NAN]).is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! Reading columns out of HDF5 datasets.
+//! Only available with the feature `hdf5`, otherwise every function panics
+//! with a hint on how to enable it
+
+#[cfg(feature = "hdf5")]
+use hdf5::{File, H5Type, Dataset};
+
+#[cfg(feature = "hdf5")]
+fn open_dataset(path: &str, dataset: &str) -> Dataset
+{
+    let file = match File::open(path){
+        Ok(f) => f,
+        Err(e) => panic!("Unable to open hdf5 file {:?}. Error: {:?}", path, e)
+    };
+    match file.dataset(dataset){
+        Ok(d) => d,
+        Err(e) => panic!("Unable to open dataset {:?} in {:?}. Error: {:?}", dataset, path, e)
+    }
+}
+
+/// returns (rows, columns). A 1D dataset is treated as a single column
+#[cfg(feature = "hdf5")]
+fn rows_and_cols(dataset: &Dataset, name: &str) -> (usize, usize)
+{
+    let shape = dataset.shape();
+    match shape.len(){
+        1 => (shape[0], 1),
+        2 => (shape[0], shape[1]),
+        _ => panic!("Dataset {:?} has to be 1D or 2D, but has shape {:?}", name, shape)
+    }
+}
+
+#[cfg(feature = "hdf5")]
+fn read_generic<T, I>(path: &str, dataset: &str, sorted_index_iter: I) -> Vec<Vec<T>>
+where T: H5Type + Copy,
+    I: Iterator<Item=usize>
+{
+    let data = open_dataset(path, dataset);
+    let (rows, cols) = rows_and_cols(&data, dataset);
+    let raw: Vec<T> = match data.read_raw(){
+        Ok(raw) => raw,
+        Err(e) => panic!("Unable to read dataset {:?} in {:?}. Error: {:?}", dataset, path, e)
+    };
+
+    sorted_index_iter
+        .map(
+            |index|
+            {
+                if index >= cols {
+                    panic!("Error, column {:?} does not exist in dataset {:?} of {:?}", index, dataset, path)
+                }
+                (0..rows)
+                    .map(|row| raw[row * cols + index])
+                    .collect()
+            }
+        ).collect()
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn missing_feature() -> !
+{
+    panic!("Reading HDF5 files requires the feature `hdf5`. Compile with `cargo b --release --features hdf5`")
+}
+
+/// Read the requested columns of the dataset as floats
+pub fn read_columns<I>(path: &str, dataset: &str, sorted_index_iter: I) -> Vec<Vec<f64>>
+where I: Iterator<Item=usize>
+{
+    #[cfg(feature = "hdf5")]
+    {
+        read_generic(path, dataset, sorted_index_iter)
+    }
+    #[cfg(not(feature = "hdf5"))]
+    {
+        let _ = (path, dataset, sorted_index_iter);
+        missing_feature()
+    }
+}
+
+/// Read the requested columns of the dataset as histogram bins
+pub fn read_bin_columns<I>(path: &str, dataset: &str, sorted_index_iter: I) -> Vec<Vec<isize>>
+where I: Iterator<Item=usize>
+{
+    #[cfg(feature = "hdf5")]
+    {
+        read_generic::<i64, _>(path, dataset, sorted_index_iter)
+            .into_iter()
+            .map(
+                |col|
+                col.into_iter()
+                    .map(|v| v as isize)
+                    .collect()
+            ).collect()
+    }
+    #[cfg(not(feature = "hdf5"))]
+    {
+        let _ = (path, dataset, sorted_index_iter);
+        missing_feature()
+    }
+}
+
+/// Number of rows of the dataset
+pub fn count_rows(path: &str, dataset: &str) -> usize
+{
+    #[cfg(feature = "hdf5")]
+    {
+        let data = open_dataset(path, dataset);
+        rows_and_cols(&data, dataset).0
+    }
+    #[cfg(not(feature = "hdf5"))]
+    {
+        let _ = (path, dataset);
+        missing_feature()
+    }
+}
@@ -0,0 +1,32 @@
+use std::{fs::File, io::{Read, stdin}, sync::atomic::{AtomicBool, Ordering}};
+
+/// The path that is used to read from stdin instead of a file
+pub const STDIN_PATH: &str = "-";
+
+static STDIN_USED: AtomicBool = AtomicBool::new(false);
+
+/// Reads the whole file (or named pipe) into a String.
+/// If `path` is "-", stdin will be read instead.
+/// Note: stdin can only be read once, i.e., either the job or one of the files
+pub fn read_input(path: &str) -> String
+{
+    let mut content = String::new();
+    if path == STDIN_PATH {
+        if STDIN_USED.swap(true, Ordering::SeqCst) {
+            panic!("stdin can only be read once - only one input is allowed to use path {:?}", STDIN_PATH);
+        }
+        stdin()
+            .lock()
+            .read_to_string(&mut content)
+            .expect("unable to read stdin");
+    } else {
+        let mut file = match File::open(path){
+            Ok(f) => f,
+            Err(e) => panic!("unable to open file {:?}: {:?}", path, e)
+        };
+        if let Err(e) = file.read_to_string(&mut content){
+            panic!("unable to read file {:?}: {:?}", path, e)
+        }
+    }
+    content
+}
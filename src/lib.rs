@@ -0,0 +1,30 @@
+//! Merging of logarithmic probability densities from Wang Landau, 
+//! Entropic Sampling or simple sampling simulations.
+//!
+//! Besides the command line tool, jobs can be created in code,
+//! see [JobBuilder], which also allows merging intervals that are already in memory
+
+mod file_parser;
+mod parser;
+mod worker;
+mod hdf5_reader;
+mod checkpoint;
+mod input;
+mod output;
+mod cli;
+mod builder;
+mod log_cols;
+mod template;
+mod batch;
+mod detect;
+#[cfg(feature = "python")]
+mod python;
+
+pub use file_parser::*;
+pub use parser::*;
+pub use worker::*;
+pub use input::*;
+pub use output::*;
+pub use cli::*;
+pub use builder::*;
+pub use batch::*;
@@ -0,0 +1,257 @@
+//! Compact syntax for `log_cols` in the job file, e.g.
+//! `["3..27", {"index": 5, "trim_left": 2}]` or `"3.."`.
+//! Everything is expanded into [LogCol]s while parsing
+
+use std::fmt;
+use serde::{Deserialize, Deserializer, de::{self, Visitor, MapAccess, SeqAccess, value::MapAccessDeserializer}};
+use schemars::JsonSchema;
+
+use crate::LogCol;
+
+/// An entry of `log_cols`
+#[derive(Debug, Clone, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum LogColEntry
+{
+    /// Index of a single column
+    Index(usize),
+    /// Range of columns: "3..27" (27 excluded), "3..=27" (27 included)
+    /// or "3.." (until the last column of the file)
+    Range(String),
+    /// A column with options. Overwrites the columns of indices and ranges with the same index
+    Col(LogCol)
+}
+
+/// Only used for the JSON Schema: `log_cols` can be a single entry or an array of entries
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub(crate) enum LogColsSchema
+{
+    Many(Vec<LogColEntry>),
+    One(LogColEntry)
+}
+
+/// returns start and (exclusive) end. No end means: until the last column
+pub(crate) fn parse_range(range: &str) -> Result<(usize, Option<usize>), String>
+{
+    let error = || format!("invalid range of columns {:?}, use e.g. \"3..27\", \"3..=27\" or \"3..\"", range);
+    let (start, end) = range.split_once("..")
+        .ok_or_else(error)?;
+    let start: usize = start.trim()
+        .parse()
+        .map_err(|_| error())?;
+    let end = end.trim();
+    if end.is_empty() {
+        return Ok((start, None));
+    }
+    let end = match end.strip_prefix('='){
+        Some(inclusive) => inclusive.trim().parse::<usize>().map(|e| e + 1),
+        None => end.parse()
+    }.map_err(|_| error())?;
+    if end <= start {
+        return Err(format!("range of columns {:?} is empty", range));
+    }
+    Ok((start, Some(end)))
+}
+
+struct EntryVisitor;
+
+impl<'de> Visitor<'de> for EntryVisitor
+{
+    type Value = LogColEntry;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result
+    {
+        formatter.write_str("a column index, a range like \"3..27\" or \"3..\", or a log column object")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E>
+    {
+        Ok(LogColEntry::Index(v as usize))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E>
+    {
+        usize::try_from(v)
+            .map(LogColEntry::Index)
+            .map_err(|_| E::custom(format!("invalid column index {}", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E>
+    {
+        parse_range(v)
+            .map_err(E::custom)?;
+        Ok(LogColEntry::Range(v.to_owned()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error>
+    {
+        LogCol::deserialize(MapAccessDeserializer::new(map))
+            .map(LogColEntry::Col)
+    }
+}
+
+impl<'de> Deserialize<'de> for LogColEntry
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+struct LogColsVisitor;
+
+impl<'de> Visitor<'de> for LogColsVisitor
+{
+    type Value = Vec<LogColEntry>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result
+    {
+        formatter.write_str("an array of log columns, or a single column index or range")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = seq.next_element()? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E>
+    {
+        EntryVisitor.visit_u64(v).map(|e| vec![e])
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E>
+    {
+        EntryVisitor.visit_i64(v).map(|e| vec![e])
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E>
+    {
+        EntryVisitor.visit_str(v).map(|e| vec![e])
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error>
+    {
+        EntryVisitor.visit_map(map).map(|e| vec![e])
+    }
+}
+
+/// Expands the entries. Columns specified as object overwrite
+/// the columns of indices and ranges with the same index
+fn expand(entries: Vec<LogColEntry>) -> Vec<LogCol>
+{
+    let mut compact = Vec::new();
+    let mut explicit = Vec::new();
+    for entry in entries
+    {
+        match entry{
+            LogColEntry::Index(index) => compact.push(LogCol::new(index)),
+            LogColEntry::Range(range) => {
+                match parse_range(&range).unwrap(){
+                    (start, Some(end)) => compact.extend((start..end).map(LogCol::new)),
+                    (start, None) => {
+                        let mut col = LogCol::new(start);
+                        col.till_end = Some(true);
+                        compact.push(col);
+                    }
+                }
+            },
+            LogColEntry::Col(col) => explicit.push(col)
+        }
+    }
+
+    for col in compact
+    {
+        match explicit.iter_mut().find(|c| c.index == col.index){
+            // keep "3.." working, even if column 3 is specified explicitly
+            Some(c) => c.till_end = c.till_end.or(col.till_end),
+            None => explicit.push(col)
+        }
+    }
+    explicit
+}
+
+pub(crate) fn deserialize_log_cols<'de, D>(deserializer: D) -> Result<Vec<LogCol>, D::Error>
+where D: Deserializer<'de>
+{
+    deserializer.deserialize_any(LogColsVisitor)
+        .map(expand)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn cols(json: &str) -> Vec<LogCol>
+    {
+        deserialize_log_cols(&mut serde_json::Deserializer::from_str(json)).unwrap()
+    }
+
+    fn indices(cols: &[LogCol]) -> Vec<usize>
+    {
+        cols.iter().map(|c| c.index).collect()
+    }
+
+    #[test]
+    fn ranges()
+    {
+        assert_eq!(parse_range("3..27"), Ok((3, Some(27))));
+        assert_eq!(parse_range("3..=27"), Ok((3, Some(28))));
+        assert_eq!(parse_range(" 3 .. = 27 "), Ok((3, Some(28))));
+        assert_eq!(parse_range("3.."), Ok((3, None)));
+        assert!(parse_range("3").is_err());
+        assert!(parse_range("..3").is_err());
+        assert!(parse_range("a..3").is_err());
+        assert!(parse_range("3..3").is_err());
+        assert!(parse_range("3..=2").is_err());
+    }
+
+    #[test]
+    fn single_entries()
+    {
+        assert_eq!(indices(&cols("4")), vec![4]);
+        assert_eq!(indices(&cols("\"3..=5\"")), vec![3, 4, 5]);
+        let till_end = cols("\"3..\"");
+        assert_eq!(indices(&till_end), vec![3]);
+        assert_eq!(till_end[0].till_end, Some(true));
+    }
+
+    #[test]
+    fn duplicates_are_removed()
+    {
+        assert_eq!(indices(&cols("[\"3..6\", 4, 5, \"5..=6\"]")), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn objects_override_compact_entries()
+    {
+        let cols = cols("[\"3..6\", {\"index\": 4, \"trim_left\": 2}, {\"index\": 3, \"enabled\": false}]");
+        assert_eq!(indices(&cols), vec![4, 3, 5]);
+        assert_eq!(cols[0].trim_left, Some(2));
+        assert_eq!(cols[1].enabled, Some(false));
+        assert_eq!(cols[2].trim_left, None);
+    }
+
+    #[test]
+    fn till_end_is_kept_for_objects()
+    {
+        let cols = cols("[\"3..\", {\"index\": 3, \"trim_left\": 20}]");
+        assert_eq!(indices(&cols), vec![3]);
+        assert_eq!(cols[0].trim_left, Some(20));
+        assert_eq!(cols[0].till_end, Some(true));
+    }
+
+    #[test]
+    fn invalid_entries()
+    {
+        assert!(deserialize_log_cols(&mut serde_json::Deserializer::from_str("-1")).is_err());
+        assert!(deserialize_log_cols(&mut serde_json::Deserializer::from_str("[\"3-5\"]")).is_err());
+        assert!(deserialize_log_cols(&mut serde_json::Deserializer::from_str("[{\"index\": 3, \"typo\": 1}]")).is_err());
+    }
+}
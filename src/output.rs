@@ -0,0 +1,427 @@
+use std::{io::Write, str::FromStr, time::{SystemTime, UNIX_EPOCH}};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+use crate::{IntervalInfo, Interval, MergeType, Job};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, JsonSchema)]
+pub enum OutputFormat
+{
+    /// Space separated, with `#` header. This is the default
+    #[default]
+    Text,
+    /// Comma separated, with header
+    Csv,
+    /// Json containing the bins, the merged curve, the aligned intervals and metadata
+    Json,
+    /// Numpy array (.npy) of shape (bins, 2 + intervals).
+    /// The columns are: bin, merged, aligned intervals
+    Npy
+}
+
+impl FromStr for OutputFormat
+{
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+        {
+            "text" | "Text" | "TEXT" => Ok(Self::Text),
+            "csv" | "Csv" | "CSV" => Ok(Self::Csv),
+            "json" | "Json" | "JSON" => Ok(Self::Json),
+            "npy" | "Npy" | "NPY" => Ok(Self::Npy),
+            _ => Err("Invalid output format. Options are 'Text', 'Csv', 'Json' or 'Npy'")
+        }
+    }
+}
+
+/// The merged curve and the aligned intervals,
+/// where every aligned interval has the same length as the merged curve (padded with NaN).
+/// Returned by [Job::glue], written via [Job::write]
+#[derive(Debug, Clone)]
+pub struct MergeResult
+{
+    pub bins: Vec<f64>,
+    /// logarithm (base 10) of the merged probability
+    pub merged: Vec<f64>,
+    pub aligned: Vec<Vec<f64>>,
+    /// Source of each of the aligned intervals, same order as `aligned`
+    pub intervals: Vec<IntervalInfo>,
+    /// Lines of the input files starting with `carry_prefix` of the job
+    pub carried: Vec<CarriedLines>,
+    /// the histogram bin corresponding to the first entry, i.e., before rescaling the bins
+    pub first_bin: isize
+}
+
+/// How the merged logarithmic probability density should be normalized
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub enum Normalization
+{
+    /// Do not normalize, keep the result of the merge as it is
+    Raw,
+    /// The sum of the probabilities is 1
+    Sum,
+    /// The integral of the probability density is 1, 
+    /// i.e., the sum of the probabilities times `bin_size` (1 if not specified)
+    Integral,
+    /// Shift, such that the logarithmic probability at `bin` (before the bins are rescaled via `bin_size`) equals `value`
+    Anchor{
+        bin: isize,
+        value: f64
+    },
+    /// Shift, such that the merged curve agrees with the reference intervals over their range,
+    /// i.e., the mean difference between the reference and the merged curve is 0.
+    /// Mark the reference intervals via `reference` in the file info or log column.
+    /// This is the default if any interval is marked as reference
+    Reference
+}
+
+/// log10 of the sum of 10^val, ignoring NaNs
+fn log10_sum(values: &[f64]) -> f64
+{
+    let max = values.iter()
+        .copied()
+        .filter(|v| !v.is_nan())
+        .fold(f64::NEG_INFINITY, f64::max);
+    let sum: f64 = values.iter()
+        .filter(|v| !v.is_nan())
+        .map(|v| 10_f64.powf(v - max))
+        .sum();
+    max + sum.log10()
+}
+
+impl MergeResult
+{
+    /// `intervals` have to be in the order in which they were glued.
+    /// If `bin_size` is given, the bins are rescaled to `bin_starting_point + i * bin_size`.
+    /// Note: The probabilities are not changed here, see [MergeResult::normalize]
+    pub fn new(
+        merged: &[f64],
+        aligned: &[Vec<f64>],
+        intervals: &[Interval],
+        bin_size: Option<f64>,
+        bin_starting_point: f64
+    ) -> Self
+    {
+        let left = intervals.iter()
+            .map(|i| i.info.left)
+            .min()
+            .expect("No intervals");
+
+        let aligned = aligned.iter()
+            .zip(intervals.iter())
+            .map(
+                |(values, interval)|
+                {
+                    if values.len() == merged.len() {
+                        return values.clone();
+                    }
+                    let offset = (interval.info.left - left) as usize;
+                    let mut padded = vec![f64::NAN; merged.len()];
+                    padded[offset..offset + values.len()].copy_from_slice(values);
+                    padded
+                }
+            ).collect();
+
+        let mut table = Self{
+            bins: (0..merged.len()).map(|i| (left + i as isize) as f64).collect(),
+            merged: merged.to_vec(),
+            aligned,
+            intervals: intervals.iter().map(|i| i.info.clone()).collect(),
+            carried: Vec::new(),
+            first_bin: left
+        };
+
+        if let Some(bin_size) = bin_size {
+            table.bins.iter_mut()
+                .enumerate()
+                .for_each(|(i, bin)| *bin = bin_starting_point + i as f64 * bin_size);
+        }
+        table
+    }
+
+    /// Mean difference between the reference intervals and the merged curve
+    fn reference_shift(&self, intervals: &[Interval]) -> f64
+    {
+        let mut sum = 0.0;
+        let mut count = 0_usize;
+        for interval in intervals.iter().filter(|i| i.info.reference)
+        {
+            let offset = (interval.info.left - self.first_bin) as usize;
+            interval.log_prob
+                .iter()
+                .zip(self.merged[offset..].iter())
+                .map(|(reference, merged)| reference - merged)
+                .filter(|diff| diff.is_finite())
+                .for_each(
+                    |diff|
+                    {
+                        sum += diff;
+                        count += 1;
+                    }
+                );
+        }
+        if count == 0 {
+            panic!("Normalization 'Reference' requires at least one interval marked as reference with valid entries");
+        }
+        sum / count as f64
+    }
+
+    /// Adds `shift` to the merged curve and all aligned intervals
+    pub fn shift(&mut self, shift: f64)
+    {
+        self.merged.iter_mut()
+            .chain(self.aligned.iter_mut().flatten())
+            .for_each(|val| *val += shift);
+    }
+
+    /// Without `normalization` the probability is normalized as density, 
+    /// i.e., divided by `bin_size`, if the `bin_size` is given. Otherwise it is not changed.
+    /// `intervals` are only used for [Normalization::Reference]
+    pub fn normalize(&mut self, normalization: Option<Normalization>, bin_size: Option<f64>, intervals: &[Interval])
+    {
+        let shift = match normalization{
+            None => {
+                match bin_size{
+                    Some(bin_size) => -bin_size.log10(),
+                    None => return
+                }
+            },
+            Some(Normalization::Raw) => return,
+            Some(Normalization::Sum) => -log10_sum(&self.merged),
+            Some(Normalization::Integral) => {
+                -log10_sum(&self.merged) - bin_size.unwrap_or(1.0).log10()
+            },
+            Some(Normalization::Anchor{bin, value}) => {
+                let current = usize::try_from(bin - self.first_bin)
+                    .ok()
+                    .and_then(|index| self.merged.get(index))
+                    .unwrap_or_else(|| panic!("Anchor bin {} is outside of the merged interval", bin));
+                if current.is_nan(){
+                    panic!("Merged logarithmic probability at anchor bin {} is NaN", bin);
+                }
+                value - current
+            },
+            Some(Normalization::Reference) => self.reference_shift(intervals)
+        };
+        self.shift(shift);
+    }
+
+    fn header(&self) -> Vec<String>
+    {
+        let mut header = vec!["bin".to_owned(), "log_merged".to_owned()];
+        header.extend(
+            (0..self.aligned.len())
+                .map(|i| format!("log_interval{}", i))
+        );
+        header
+    }
+
+    /// Removes the aligned intervals, so that only the merged curve will be written
+    pub fn only_merged(&mut self)
+    {
+        self.aligned.clear();
+    }
+
+    pub fn write_text<W: Write>(&self, mut writer: W) -> std::io::Result<()>
+    {
+        writeln!(writer, "#{}", self.header().join(" "))?;
+        writeln!(writer, "#log: Base10")?;
+        for (index, bin) in self.bins.iter().enumerate()
+        {
+            write!(writer, "{} {:e}", bin, self.merged[index])?;
+            for interval in self.aligned.iter()
+            {
+                write!(writer, " {:e}", interval[index])?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the aligned intervals in long format, i.e., one line per bin and interval.
+    /// NaNs are skipped
+    pub fn write_long<W: Write>(&self, mut writer: W, csv: bool) -> std::io::Result<()>
+    {
+        let sep = if csv {","} else {" "};
+        let header = ["bin", "interval_id", "source_path", "column", "value"].join(sep);
+        if csv {
+            writeln!(writer, "{}", header)?;
+        } else {
+            writeln!(writer, "#{}", header)?;
+        }
+        for (id, (values, info)) in self.aligned.iter().zip(self.intervals.iter()).enumerate()
+        {
+            for (bin, val) in self.bins.iter().zip(values.iter())
+            {
+                if val.is_nan(){
+                    continue;
+                }
+                writeln!(
+                    writer, 
+                    "{bin}{sep}{id}{sep}{path}{sep}{column}{sep}{val:e}",
+                    bin = bin,
+                    sep = sep,
+                    id = id,
+                    path = info.path,
+                    column = info.column,
+                    val = val
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()>
+    {
+        writeln!(writer, "{}", self.header().join(","))?;
+        for (index, bin) in self.bins.iter().enumerate()
+        {
+            write!(writer, "{},{:e}", bin, self.merged[index])?;
+            for interval in self.aligned.iter()
+            {
+                write!(writer, ",{:e}", interval[index])?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W, metadata: &Provenance) -> serde_json::Result<()>
+    {
+        let json = JsonOutput{
+            bins: &self.bins,
+            log_merged: &self.merged,
+            aligned: &self.aligned,
+            metadata
+        };
+        serde_json::to_writer_pretty(writer, &json)
+    }
+
+    /// Writes the table as npy file (format version 1.0)
+    pub fn write_npy<W: Write>(&self, mut writer: W) -> std::io::Result<()>
+    {
+        let cols = 2 + self.aligned.len();
+        let mut header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+            self.bins.len(),
+            cols
+        );
+        // magic string (6) + version (2) + header length (2) + header + newline
+        // has to be divisible by 64
+        let unpadded = 10 + header.len() + 1;
+        let padding = (64 - unpadded % 64) % 64;
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+
+        writer.write_all(b"\x93NUMPY\x01\x00")?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+
+        for (index, bin) in self.bins.iter().enumerate()
+        {
+            writer.write_all(&bin.to_le_bytes())?;
+            writer.write_all(&self.merged[index].to_le_bytes())?;
+            for interval in self.aligned.iter()
+            {
+                writer.write_all(&interval[index].to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lines of a source file that are carried through to the output
+#[derive(Serialize, Debug, Clone)]
+pub struct CarriedLines
+{
+    pub path: String,
+    pub lines: Vec<String>
+}
+
+/// Describes how the output was created
+#[derive(Serialize, Debug)]
+pub struct Provenance<'a>
+{
+    pub version: &'static str,
+    pub log_base: &'static str,
+    pub merge: MergeType,
+    pub intervals: Vec<&'a IntervalInfo>,
+    /// Seconds since the unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<&'a Job>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub carried: Vec<CarriedLines>
+}
+
+impl<'a> Provenance<'a>
+{
+    /// Without `full` only the version, log base, merge type and the intervals are included
+    pub fn new(job: &'a Job, result: &'a MergeResult, full: bool) -> Self
+    {
+        let mut provenance = Self{
+            version: env!("CARGO_PKG_VERSION"),
+            log_base: "Base10",
+            merge: job.merge,
+            intervals: result.intervals.iter().collect(),
+            created: None,
+            job: None,
+            carried: Vec::new()
+        };
+        if full {
+            provenance.created = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs());
+            provenance.job = Some(job);
+            provenance.carried = result.carried.clone();
+        }
+        provenance
+    }
+
+    /// Writes the provenance as comment lines starting with `#`
+    pub fn write_header<W: Write>(&self, mut writer: W) -> std::io::Result<()>
+    {
+        writeln!(writer, "#large_deviation_merger v{}", self.version)?;
+        if let Some(created) = self.created {
+            writeln!(writer, "#created: {} (seconds since unix epoch)", created)?;
+        }
+        writeln!(writer, "#merge: {:?} log: {}", self.merge, self.log_base)?;
+        if let Some(job) = self.job {
+            let job = serde_json::to_string(job)
+                .expect("unable to serialize job");
+            writeln!(writer, "#job: {}", job)?;
+        }
+        for (index, info) in self.intervals.iter().enumerate()
+        {
+            writeln!(
+                writer, 
+                "#interval{}: bins [{}, {}] column {} of {}", 
+                index, 
+                info.left, 
+                info.right, 
+                info.column, 
+                info.path
+            )?;
+        }
+        for carried in self.carried.iter()
+        {
+            for line in carried.lines.iter()
+            {
+                writeln!(writer, "#{}: {}", carried.path, line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a>
+{
+    bins: &'a [f64],
+    log_merged: &'a [f64],
+    aligned: &'a [Vec<f64>],
+    metadata: &'a Provenance<'a>
+}
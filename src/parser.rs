@@ -1,17 +1,19 @@
-use std::{fs::File, io::BufReader, str::FromStr};
-use serde::{Deserialize, Serialize};
-use serde_json::{from_reader, Value};
+use std::{str::FromStr, io::Write, path::Path, collections::BTreeMap};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned};
+use schemars::JsonSchema;
 
 use crate::*;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, JsonSchema)]
 pub enum HistType
 {
+    #[default]
     HistIsizeFast
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, JsonSchema)]
 pub enum MergeType{
+    #[default]
     Average,
     Derivative
 }
@@ -29,156 +31,107 @@ impl FromStr for MergeType
     }
 }
 
-impl Default for HistType
+pub(crate) fn out_default_value() -> String
 {
-    fn default() -> Self {
-        Self::HistIsizeFast
-    }
+    "merged.glued".to_owned()
 }
 
-pub fn parse(file: &str) -> Job
+pub(crate) fn default_out() -> String
 {
-    let file = File::open(file)
-        .expect("unable to open file");
-    
-    let reader = BufReader::new(file);
-
-    let json: Value = from_reader(reader)
-        .expect("Invalid Json");
-
-    let file_infos_json = json.get("files")
-        .expect("Json is missing array 'files'");
-    
-    assert!(file_infos_json.is_array(), "'files' must be an array of file infos!");
-    let file_array = file_infos_json.as_array().unwrap();
-
-    let comment = match json.get("global_comment")
-    {
-        Some(v) => {
-            if v.is_null() {
-                None
-            } else {
-                Some(v.as_str().expect("Invalid 'global_comment'").to_owned())
-            }
-        },
-        None => None
-    };
-
+    let def = out_default_value();
+    eprintln!("Missing output name ('out') - using default - {}", def);
+    def
+}
 
-    let mut file_infos: Vec<FileInfo> = file_array.iter()
-        .map(
-            |obj| 
-            {
-                serde_json::from_value(obj.clone())
-                    .expect("Invalid Json in 'files' array")
-            }
-            )
-        .collect();
+pub(crate) fn default_hist() -> HistType
+{
+    eprintln!("Warning, no hist type ('hist') specified, using default: {:?}", HistType::default());
+    HistType::default()
+}
 
-    if comment.is_some() {
-        file_infos.iter_mut()
-            .filter(|file_info| file_info.comment.is_none())
-            .for_each(
-                |f|
-                f.comment = comment.clone()
-            );
+/// Accepts a number or a string containing a number, e.g., for `bin_size`
+pub(crate) fn float_or_string<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where D: Deserializer<'de>
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FloatOrString{
+        Float(f64),
+        String(String)
     }
 
-    file_infos.iter_mut().for_each(FileInfo::sort_cols);
-
-    let hist_type = match json.get("hist"){
-        Some(val) => {
-            match serde_json::from_value(val.clone()){
-                Ok(hist) => hist,
-                Err(e) => {
-                    eprintln!("Error: {:?} Invalid Hist Type - currently only one type is implemented, so I will fallback to the default", e);
-                    // TODO Print out valid types
-                    HistType::default()
-                }
-            }
-        },
-        None => {
-            eprintln!("Warning, no hist type ('hist') specified, using default: {:?}", HistType::default());
-            HistType::default()
+    match Option::<FloatOrString>::deserialize(deserializer)?{
+        None => Ok(None),
+        Some(FloatOrString::Float(f)) => Ok(Some(f)),
+        Some(FloatOrString::String(s)) => {
+            s.trim()
+                .parse()
+                .map(Some)
+                .map_err(|_| serde::de::Error::custom(format!("invalid number {:?}", s)))
         }
-    };
+    }
+}
+
+/// Format of a job file
+#[derive(Debug, Clone, Copy)]
+pub enum JobFormat
+{
+    Json,
+    Toml,
+    Yaml
+}
 
-    let out = match json.get("out")
+impl JobFormat
+{
+    /// Determined by the extension, Json if the extension is unknown (or for stdin)
+    pub fn from_path(path: &str) -> Self
     {
-        Some(v) => {
-            match v.as_str(){
-                Some(out) => out.to_owned(),
-                None => panic!("Invalid output type - should be string. Note: This is the file that will be created for the output")
-            }
-        },
-        None => {
-            let def = "merged.glued";
-            eprintln!("Missing output name ('out') - using default - {}", def);
-            def.to_owned()                        
+        match Path::new(path).extension().and_then(|e| e.to_str()){
+            Some("toml") => Self::Toml,
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Json
         }
-    };
+    }
 
-    let merge: MergeType = match json.get("merge")
+    pub fn deserialize<T: DeserializeOwned>(self, content: &str) -> Result<T, String>
     {
-        Some(v) => {
-            serde_json::from_value(v.clone())
-                .expect("Invalid Merge type ('merge')")
-        },
-        None => MergeType::Average
-    };
-
-    let bin_size = match json.get("bin_size"){
-        None => None,
-        Some(v) => {
-            if v.is_null() {
-                None
-            } else {
-                Some(
-                    match v.as_f64(){
-                        Some(v) => v,
-                        None => {
-                            let error = "bin_size parsing error";
-                            let s = v.as_str()
-                                .expect(error);
-                            s.parse()
-                                .expect(error)
-                        }
-                    }
-                )
-            }
+        match self{
+            Self::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string())
         }
-    };
+    }
 
-    let bin_start = match json.get("bin_starting_point")
+    pub fn write_job<W: Write>(self, job: &Job, mut writer: W) -> std::io::Result<()>
     {
-        None => None,
-        Some(v) => {
-            if v.is_null() {
-                None
-            } else {
-                Some(
-                    match v.as_f64(){
-                        Some(v) => v,
-                        None => {
-                            let error = "bin_starting_point parsing error";
-                            let s = v.as_str()
-                                .expect(error);
-                            s.parse()
-                                .expect(error)
-                        }
-                    }
-                )
-            }
+        match self{
+            Self::Json => serde_json::to_writer_pretty(&mut writer, job)?,
+            Self::Toml => {
+                // going via toml::Value, as it takes care of writing the values before the tables
+                let value = toml::Value::try_from(job)
+                    .expect("unable to convert job to toml");
+                let s = toml::to_string_pretty(&value)
+                    .expect("unable to convert job to toml");
+                writer.write_all(s.as_bytes())?
+            },
+            Self::Yaml => serde_yaml::to_writer(&mut writer, job)
+                .expect("unable to convert job to yaml")
         }
-    };
-
-    Job { 
-        out, 
-        hist: hist_type,
-        files: file_infos, 
-        merge,
-        global_comment: comment,
-        bin_size,
-        bin_starting_point: bin_start
+        writeln!(writer)
     }
-}
\ No newline at end of file
+}
+
+/// Parses the job file. Json, Toml or Yaml, depending on the extension. 
+/// Unknown fields (e.g. typos) are errors, which are reported with line and column
+pub fn parse(file: &str) -> Job
+{
+    parse_with_variables(file, &BTreeMap::new())
+}
+
+/// Same as [parse], but `variables` overwrite the variables defined in the job file
+pub fn parse_with_variables(file: &str, variables: &BTreeMap<String, String>) -> Job
+{
+    let mut job = template::load_job(file, variables);
+    job.prepare_files();
+    job
+}
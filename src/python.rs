@@ -0,0 +1,183 @@
+//! Python bindings, only available with the feature `python`.
+//! Build the python module with `maturin develop --release`
+//!
+//! Errors (i.e., panics) are raised as `ValueError` (invalid job file) 
+//! or `RuntimeError` (merging and writing failed) in python
+
+// false positive of the pyo3 0.22 macros for methods returning `PyResult`
+#![allow(clippy::useless_conversion)]
+
+use pyo3::{prelude::*, types::PyDict, exceptions::{PyValueError, PyRuntimeError}};
+use std::{str::FromStr, panic::{catch_unwind, AssertUnwindSafe}};
+
+use crate::{*, batch::panic_message};
+
+/// Turns a panic of `f` into an exception that can be caught via `except Exception`, 
+/// unlike the `PanicException` of pyo3
+fn catch<T>(f: impl FnOnce() -> T, error: fn(String) -> PyErr) -> PyResult<T>
+{
+    catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| error(panic_message(payload)))
+}
+
+/// A merge job. Either empty (add in-memory intervals) or loaded from a job file
+#[pyclass(name = "Job")]
+struct PyJob
+{
+    job: Job
+}
+
+#[pymethods]
+impl PyJob
+{
+    #[new]
+    fn new() -> Self
+    {
+        Self{
+            job: JobBuilder::new().build()
+        }
+    }
+
+    /// Load a job file, just like the `merge` subcommand
+    #[staticmethod]
+    fn from_file(path: &str) -> PyResult<Self>
+    {
+        let job = catch(|| parse(path), PyValueError::new_err)?;
+        Ok(Self{job})
+    }
+
+    /// Add an interval. `log_prob` (list or numpy array) is the logarithm (base 10) of the probability,
+    /// where `log_prob[0]` corresponds to the bin `left`
+    #[pyo3(signature = (left, log_prob, reference = false))]
+    fn add_interval(&mut self, left: isize, log_prob: Vec<f64>, reference: bool) -> PyResult<()>
+    {
+        catch(|| self.job.add_interval(left, log_prob, reference), PyValueError::new_err)
+    }
+
+    #[getter]
+    fn get_out(&self) -> String
+    {
+        self.job.out.clone()
+    }
+
+    #[setter]
+    fn set_out(&mut self, out: String)
+    {
+        self.job.out = out;
+    }
+
+    /// "Average" or "Derivative"
+    #[setter]
+    fn set_merge(&mut self, merge: &str) -> PyResult<()>
+    {
+        self.job.merge = MergeType::from_str(merge)
+            .map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_bin_size(&mut self, bin_size: Option<f64>)
+    {
+        self.job.bin_size = bin_size;
+    }
+
+    #[setter]
+    fn set_bin_starting_point(&mut self, bin_starting_point: Option<f64>)
+    {
+        self.job.bin_starting_point = bin_starting_point;
+    }
+
+    /// Same as in the job file, e.g., "Sum" or '{"Anchor": {"bin": 100, "value": -3.2}}'
+    #[setter]
+    fn set_normalization(&mut self, normalization: Option<&str>) -> PyResult<()>
+    {
+        self.job.normalization = match normalization{
+            None => None,
+            Some(n) => {
+                let parsed = serde_json::from_str(n)
+                    .or_else(|_| serde_json::from_value(serde_json::Value::String(n.to_owned())))
+                    .map_err(|e| PyValueError::new_err(format!("Invalid normalization: {}", e)))?;
+                Some(parsed)
+            }
+        };
+        Ok(())
+    }
+
+    /// Merge and return the result instead of writing it to `out`
+    fn glue(&self) -> PyResult<PyMergeResult>
+    {
+        let result = catch(|| self.job.glue(), PyRuntimeError::new_err)?;
+        Ok(PyMergeResult{result})
+    }
+
+    /// Write a result into `out`, as specified by the job
+    fn write(&self, result: &PyMergeResult) -> PyResult<()>
+    {
+        catch(|| self.job.write(&result.result), PyRuntimeError::new_err)
+    }
+
+    /// Merge and write the result into `out`
+    fn work(&self) -> PyResult<()>
+    {
+        catch(|| self.job.work(), PyRuntimeError::new_err)
+    }
+}
+
+/// Result of [PyJob::glue]
+#[pyclass(name = "MergeResult")]
+struct PyMergeResult
+{
+    result: MergeResult
+}
+
+#[pymethods]
+impl PyMergeResult
+{
+    #[getter]
+    fn bins(&self) -> Vec<f64>
+    {
+        self.result.bins.clone()
+    }
+
+    /// logarithm (base 10) of the merged probability
+    #[getter]
+    fn merged(&self) -> Vec<f64>
+    {
+        self.result.merged.clone()
+    }
+
+    /// aligned intervals, padded with NaN to the length of `merged`
+    #[getter]
+    fn aligned(&self) -> Vec<Vec<f64>>
+    {
+        self.result.aligned.clone()
+    }
+
+    /// Source of each aligned interval as dict
+    #[getter]
+    fn intervals(&self, py: Python<'_>) -> PyResult<Vec<PyObject>>
+    {
+        self.result.intervals
+            .iter()
+            .map(
+                |info|
+                {
+                    let dict = PyDict::new_bound(py);
+                    dict.set_item("path", &info.path)?;
+                    dict.set_item("column", info.column)?;
+                    dict.set_item("left", info.left)?;
+                    dict.set_item("right", info.right)?;
+                    dict.set_item("reference", info.reference)?;
+                    Ok(dict.into())
+                }
+            ).collect()
+    }
+}
+
+#[pymodule]
+fn large_deviation_merger(m: &Bound<'_, PyModule>) -> PyResult<()>
+{
+    m.add_class::<PyJob>()?;
+    m.add_class::<PyMergeResult>()?;
+    Ok(())
+}
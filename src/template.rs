@@ -0,0 +1,336 @@
+//! Includes and variables of job files.
+//! A job file can `include` other job files (fragments), which are merged into it,
+//! and define `variables`, which are substituted for `${name}` in the strings of all job files
+
+use std::{collections::BTreeMap, path::Path};
+use serde_json::{Value, Map};
+use schemars::JsonSchema;
+
+use crate::{Job, JobFormat, read_input, STDIN_PATH};
+
+/// A job file together with all of its (recursive) includes
+struct JobSource
+{
+    path: String,
+    format: JobFormat,
+    content: String,
+    raw: Value,
+    includes: Vec<JobSource>
+}
+
+// only used for the JSON Schema
+/// Value of a variable: string, number or boolean
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+pub(crate) enum VariableSchema
+{
+    String(String),
+    Number(f64),
+    Bool(bool)
+}
+
+/// Only these fields are not substituted, everything else is part of the job
+const NOT_SUBSTITUTED: [&str; 2] = ["include", "variables"];
+
+/// Include paths are relative to the directory of the including job file
+pub(crate) fn resolve(include: &str, including: &str) -> String
+{
+    if including == STDIN_PATH || Path::new(include).is_absolute() {
+        return include.to_owned();
+    }
+    match Path::new(including).parent(){
+        Some(dir) => dir.join(include).to_str().unwrap().to_owned(),
+        None => include.to_owned()
+    }
+}
+
+impl JobSource
+{
+    fn load(path: &str, stack: &mut Vec<String>) -> Self
+    {
+        if stack.iter().any(|p| p == path) {
+            panic!("Include cycle: {:?} includes {:?}", stack, path);
+        }
+        let content = read_input(path);
+        let format = JobFormat::from_path(path);
+        let raw: Value = format.deserialize(&content)
+            .unwrap_or_else(|e| panic!("Invalid job file {:?}: {}", path, e));
+
+        let include_paths: Vec<String> = match raw.get("include"){
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(arr)) => {
+                arr.iter()
+                    .map(
+                        |inc|
+                        match inc.as_str(){
+                            Some(inc) => resolve(inc, path),
+                            None => panic!("'include' has to be an array of paths - {:?}", path)
+                        }
+                    ).collect()
+            },
+            _ => panic!("'include' has to be an array of paths - {:?}", path)
+        };
+
+        stack.push(path.to_owned());
+        let includes = include_paths.iter()
+            .map(|inc| Self::load(inc, stack))
+            .collect();
+        stack.pop();
+
+        Self{
+            path: path.to_owned(),
+            format,
+            content,
+            raw,
+            includes
+        }
+    }
+
+    /// Variables of the includes (later includes win), overwritten by the own variables
+    fn variables(&self) -> BTreeMap<String, String>
+    {
+        let mut variables = BTreeMap::new();
+        for include in self.includes.iter()
+        {
+            variables.extend(include.variables());
+        }
+        match self.raw.get("variables"){
+            None | Some(Value::Null) => (),
+            Some(Value::Object(own)) => {
+                variables.extend(
+                    own.iter()
+                        .map(|(name, value)| (name.clone(), variable_value(name, value, &self.path)))
+                );
+            },
+            _ => panic!("'variables' has to be an object of NAME: VALUE - {:?}", self.path)
+        }
+        variables
+    }
+
+    /// The substituted job of this file, merged with its includes.
+    /// Does not contain `include` and `variables`
+    fn merged_value(&self, variables: &BTreeMap<String, String>) -> Value
+    {
+        let mut merged = Value::Object(Map::new());
+        for include in self.includes.iter()
+        {
+            merge_into(&mut merged, include.merged_value(variables), true);
+        }
+        let mut own = self.raw.clone();
+        if let Value::Object(map) = &mut own {
+            for key in NOT_SUBSTITUTED
+            {
+                map.remove(key);
+            }
+        }
+        substitute_value(&mut own, variables, &self.path);
+        merge_into(&mut merged, own, true);
+        merged
+    }
+}
+
+/// Objects are merged recursively, where `other` wins.
+/// The top level `files` are concatenated instead
+fn merge_into(base: &mut Value, other: Value, top_level: bool)
+{
+    match (base, other)
+    {
+        (Value::Object(base), Value::Object(other)) => {
+            for (key, value) in other
+            {
+                match (base.get_mut(&key), value){
+                    (Some(Value::Array(files)), Value::Array(new)) if top_level && key == "files" => {
+                        files.extend(new)
+                    },
+                    (Some(existing), value) if existing.is_object() && value.is_object() => {
+                        merge_into(existing, value, false)
+                    },
+                    (_, value) => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        },
+        (base, other) => *base = other
+    }
+}
+
+/// Variables can be strings, numbers or booleans, e.g., `N: 3200` in Yaml
+fn variable_value(name: &str, value: &Value, path: &str) -> String
+{
+    match value{
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => panic!("Variable {:?} in {:?} has to be a string, number or boolean, not {}", name, path, value)
+    }
+}
+
+/// Substitutes the variables in all strings of `value` (recursively)
+fn substitute_value(value: &mut Value, variables: &BTreeMap<String, String>, path: &str)
+{
+    match value{
+        Value::String(s) if s.contains("${") => *s = substitute(s, variables, path),
+        Value::Array(arr) => arr.iter_mut()
+            .for_each(|v| substitute_value(v, variables, path)),
+        Value::Object(map) => map.values_mut()
+            .for_each(|v| substitute_value(v, variables, path)),
+        _ => ()
+    }
+}
+
+/// Replaces every `${name}` by the value of the variable
+fn substitute(text: &str, variables: &BTreeMap<String, String>, path: &str) -> String
+{
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${")
+    {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let name = after.find('}')
+            .map(|end| &after[..end])
+            .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        match name{
+            Some(name) => {
+                match variables.get(name){
+                    Some(value) => result.push_str(value),
+                    None => panic!("Undefined variable ${{{}}} in {:?}. Defined variables: {:?}", name, path, variables)
+                }
+                rest = &after[name.len() + 1..];
+            },
+            None => {
+                // not a variable, keep it
+                result.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Loads the job file including all of its includes.
+/// `variables` overwrite the variables defined in the job files
+pub(crate) fn load_job(path: &str, variables: &BTreeMap<String, String>) -> Job
+{
+    let source = JobSource::load(path, &mut Vec::new());
+    let mut all_variables = source.variables();
+    all_variables.extend(variables.clone());
+
+    let value = source.merged_value(&all_variables);
+    let plain = source.includes.is_empty()
+        && source.raw.get("variables").is_none()
+        && source.raw.get("include").is_none()
+        && value == source.raw;
+
+    let mut job: Job = if plain {
+        // nothing to merge or substitute: deserialize the text directly, so that errors contain the line and column
+        source.format.deserialize(&source.content)
+            .unwrap_or_else(|e| panic!("Invalid job file {:?}: {}", path, e))
+    } else {
+        serde_json::from_value(value)
+            .unwrap_or_else(|e| panic!("Invalid job file {:?} (after merging the includes and substituting the variables): {}", path, e))
+    };
+
+    // the job is already complete, keep the used values for the provenance
+    job.include = None;
+    if !all_variables.is_empty() {
+        job.variables = Some(all_variables);
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use serde_json::json;
+
+    fn vars(vars: &[(&str, &str)]) -> BTreeMap<String, String>
+    {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn substitute_variables()
+    {
+        let variables = vars(&[("N", "3200"), ("dir", "data")]);
+        assert_eq!(
+            substitute("${dir}/N${N}_${N}.dat", &variables, "job.json"),
+            "data/N3200_3200.dat"
+        );
+        // not variables
+        assert_eq!(substitute("$N ${} ${a b} ${N", &variables, "job.json"), "$N ${} ${a b} ${N");
+        assert_eq!(substitute("no variables", &BTreeMap::new(), "job.json"), "no variables");
+    }
+
+    #[test]
+    #[should_panic(expected = "Undefined variable ${x}")]
+    fn substitute_undefined()
+    {
+        substitute("${x}", &vars(&[("N", "3200")]), "job.json");
+    }
+
+    #[test]
+    fn substitute_only_string_values()
+    {
+        let variables = vars(&[("dir", "C:\\runs"), ("q", "a\"b"), ("N", "3200")]);
+        let mut value = json!({"out": "${dir}/${q}.dat", "bin_size": 0.5, "files": [{"path": "${N}", "log_cols": [1, "${N}.."]}]});
+        substitute_value(&mut value, &variables, "job.json");
+        assert_eq!(value, json!({"out": "C:\\runs/a\"b.dat", "bin_size": 0.5, "files": [{"path": "3200", "log_cols": [1, "3200.."]}]}));
+    }
+
+    #[test]
+    fn scalar_variables()
+    {
+        assert_eq!(variable_value("N", &json!(3200), "job.yaml"), "3200");
+        assert_eq!(variable_value("x", &json!(0.5), "job.yaml"), "0.5");
+        assert_eq!(variable_value("b", &json!(true), "job.yaml"), "true");
+        assert_eq!(variable_value("s", &json!("data"), "job.yaml"), "data");
+    }
+
+    #[test]
+    #[should_panic(expected = "has to be a string, number or boolean")]
+    fn invalid_variable()
+    {
+        variable_value("N", &json!([1]), "job.yaml");
+    }
+
+    #[test]
+    fn merge_nested_objects()
+    {
+        let mut base = json!({"out": "a.out", "normalization": {"Anchor": {"bin": 1, "value": 2.0}}, "merge": "Average"});
+        merge_into(&mut base, json!({"out": "b.out", "normalization": {"Anchor": {"bin": 5}}}), true);
+        assert_eq!(base, json!({"out": "b.out", "normalization": {"Anchor": {"bin": 5, "value": 2.0}}, "merge": "Average"}));
+    }
+
+    #[test]
+    fn merge_concatenates_files()
+    {
+        let mut base = json!({"files": [{"path": "a.dat"}]});
+        merge_into(&mut base, json!({"files": [{"path": "b.dat"}, {"path": "c.dat"}]}), true);
+        assert_eq!(base, json!({"files": [{"path": "a.dat"}, {"path": "b.dat"}, {"path": "c.dat"}]}));
+
+        // only the top level files are concatenated
+        let mut base = json!({"files": [1]});
+        merge_into(&mut base, json!({"files": [2]}), false);
+        assert_eq!(base, json!({"files": [2]}));
+
+        let mut base = json!({"log_cols": [1]});
+        merge_into(&mut base, json!({"log_cols": [2]}), true);
+        assert_eq!(base, json!({"log_cols": [2]}));
+    }
+
+    #[test]
+    fn resolve_relative_to_including_file()
+    {
+        assert_eq!(resolve("common.json", "jobs/job.json"), "jobs/common.json");
+        assert_eq!(resolve("common.json", "job.json"), "common.json");
+        assert_eq!(resolve("/abs/common.json", "jobs/job.json"), "/abs/common.json");
+        assert_eq!(resolve("common.json", STDIN_PATH), "common.json");
+    }
+}
@@ -1,38 +1,107 @@
 use sampling::{LogBase, IntervalOrder};
 use serde::{Serialize, Deserialize};
-use std::{fs::File, io::BufWriter};
+use schemars::{JsonSchema, schema_for};
+use std::{fs::File, io::BufWriter, collections::BTreeMap};
 use glob::glob;
 
-use crate::*;
+use crate::{*, template::VariableSchema};
 
 pub fn merge(task: Merge)
 {
-    let job = parser::parse(&task.json);
-    job.work();
-    println!("Success! Output written to {}", job.out)
+    let variables: BTreeMap<_, _> = task.variables
+        .iter()
+        .map(|var| parse_variable(var))
+        .collect();
+
+    let mut entries: Vec<_> = task.json
+        .iter()
+        .map(
+            |path|
+            BatchEntry{
+                path: path.clone(),
+                variables: variables.clone()
+            }
+        ).collect();
+    if let Some(manifest) = &task.manifest {
+        entries.extend(read_manifest(manifest, &variables));
+    }
+
+    match entries.len(){
+        0 => panic!("No job specified! Use --json or --manifest"),
+        1 => {
+            let out = entries[0].run(&task.overrides);
+            println!("Success! Output written to {}", out)
+        },
+        _ => {
+            if task.overrides.out.is_some() {
+                panic!("--out can not be used for multiple jobs, as they would all write into the same file");
+            }
+            if !run_batch(entries, task.threads, &task.overrides) {
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Job{
+    /// Path or url of the JSON Schema, only used by editors
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    /// Job files that are merged into this one. Objects are merged recursively,
+    /// `files` are concatenated and everything else is overwritten by this file.
+    /// Paths are relative to this job file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// Substituted for `${name}` in the strings of this job file and all included job files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<BTreeMap<String, VariableSchema>>")]
+    pub variables: Option<BTreeMap<String, String>>,
+    /// The output file that will be created
+    #[serde(default = "default_out")]
+    #[schemars(default = "out_default_value")]
     pub out: String,
+    /// The files you want to read in
     pub files: Vec<FileInfo>,
+    /// No other Histogram is implemented yet
+    #[serde(default = "default_hist")]
+    #[schemars(default)]
     pub hist: HistType,
+    #[serde(default)]
     pub merge: MergeType,
-    pub global_comment: Option<String>,
+    /// What a line must start with to be ignored, used for all files without `comment`
+    pub global_comment: Option<Comment>,
+    /// Rescale the bins to `bin_starting_point + i * bin_size`
+    #[serde(default, deserialize_with = "float_or_string")]
     pub bin_size: Option<f64>,
-    pub bin_starting_point: Option<f64>
+    /// Value of the first bin if `bin_size` is specified
+    #[serde(default, deserialize_with = "float_or_string")]
+    pub bin_starting_point: Option<f64>,
+    /// Format of the output file. Default: Text
+    pub output_format: Option<OutputFormat>,
+    /// If true, the aligned intervals are not written into `out`
+    pub only_merged: Option<bool>,
+    /// Write the aligned intervals in long format into this file:
+    /// bin, interval_id, source_path, column, value
+    pub aligned_out: Option<String>,
+    /// Write version, time, job and the source of each interval into the output.
+    /// Default: true, except for Csv and Npy (which does not support it)
+    pub provenance: Option<bool>,
+    /// Lines of the input files starting with this (e.g. "#CMD:") are copied into the provenance
+    pub carry_prefix: Option<String>,
+    /// How to normalize the result. 
+    /// Default: Divide by `bin_size` if it is specified, otherwise keep the result of the merge
+    pub normalization: Option<Normalization>,
+    /// Intervals that are already in memory, merged together with the intervals of `files`.
+    /// See [JobBuilder::interval]
+    #[serde(skip)]
+    pub intervals: Vec<Interval>
 }
 
-pub fn glob_create(options: CreateJob)
+fn glob_paths(pattern: &str) -> Vec<String>
 {
-    let right = match options.log_col_right {
-        Some(v) => v,
-        None => options.log_col_left + 1
-    };
-    if right <= options.log_col_left {
-        panic!("log_col_right must be larger than log_col_left!")
-    }
-    let files: Vec<_> = glob(&options.globbing)
+    glob(pattern)
         .expect("Error in globbing pattern")
         .filter_map(
             |entry|
@@ -43,57 +112,174 @@ pub fn glob_create(options: CreateJob)
                         println!("Warning, globbing error! {:?}", e);
                         None
                     },
-                    Ok(path) => {
-                        let path = path.to_str().unwrap().to_owned();
-                        
-                        let log_cols: Vec<_> = if options.end {
-                            vec![LogCol::new(options.log_col_left)]
-                        } else {
-                             (options.log_col_left..right)
-                                .map(LogCol::new)
-                                .collect()
-                        };
-                        
-
-                        let mut f = FileInfo{
-                            path,
-                            index_hist_left: options.hist_col_left,
-                            index_hist_right: options.hist_col_right,
-                            comment: None,
-                            sep: None,
-                            log_cols,
-                            shift: options.shift,
-                            missing: options.missing.clone()
-                        };
-                        if options.end{
-                            f.log_cols_till_end(&options.global_comment);
-                        }
-                        Some(f)
-                    }
+                    Ok(path) => Some(path.to_str().unwrap().to_owned())
                 }
             }
-        ).collect();
+        ).collect()
+}
+
+/// Fills in what was not specified for the group from its first file.
+/// Returns the paths of the group and the number of header lines to skip
+fn detect_group(group: &mut GlobGroup, no_detect: bool) -> (Vec<String>, Option<usize>)
+{
+    let paths = glob_paths(&group.pattern);
+    // only guess what was not specified
+    let detected = match paths.first(){
+        Some(first) if !no_detect => {
+            let detected = detect::detect(first);
+            detected.report(first);
+            detected
+        },
+        _ => detect::Detected::default()
+    };
+
+    if group.log_col_left.is_none() {
+        group.log_col_left = detected.log_col;
+        group.end = group.end || (group.log_col_right.is_none() && detected.till_end);
+    }
+    group.hist_col = group.hist_col.or(detected.hist_col);
+    group.comment = group.comment.take().or(detected.comment);
+    group.sep = group.sep.take().or(detected.sep);
+    (paths, detected.skip_header_lines)
+}
+
+fn group_files(
+    group: &GlobGroup,
+    paths: Vec<String>,
+    skip_header_lines: Option<usize>,
+    hist_col_right: Option<usize>,
+    global_comment: &Option<Comment>
+) -> Vec<FileInfo>
+{
+    let log_col_left = match group.log_col_left{
+        Some(left) => left,
+        None => panic!("Unable to detect the log columns of {:?}, please specify them", group.pattern)
+    };
+    let right = match group.log_col_right {
+        Some(v) => v,
+        None => log_col_left + 1
+    };
+    if !group.end && right <= log_col_left {
+        panic!("log_col_right must be larger than log_col_left!")
+    }
+    // only needed if it differs from the global comment
+    let comment = group.comment
+        .as_ref()
+        .filter(|c| !matches!(global_comment, Some(Comment::Single(g)) if g == *c))
+        .map(|c| Comment::from(c.clone()));
+
+    paths.into_iter()
+        .map(
+            |path|
+            {
+                let log_cols: Vec<_> = if group.end {
+                    let mut col = LogCol::new(log_col_left);
+                    col.till_end = Some(true);
+                    vec![col]
+                } else {
+                     (log_col_left..right)
+                        .map(LogCol::new)
+                        .collect()
+                };
+
+                let mut f = FileInfo{
+                    path,
+                    index_hist_left: group.hist_col,
+                    index_hist_right: hist_col_right,
+                    comment: comment.clone(),
+                    sep: group.sep.clone(),
+                    log_cols,
+                    shift: group.shift,
+                    missing: group.missing.clone(),
+                    format: None,
+                    inline_comments: None,
+                    skip_header_lines,
+                    min_bin: None,
+                    max_bin: None,
+                    auto_trim: None,
+                    reference: None,
+                    enabled: None,
+                    note: None
+                };
+                if group.end{
+                    f.log_cols_till_end(global_comment);
+                }
+                f
+            }
+        ).collect()
+}
+
+pub fn glob_create(options: CreateJob)
+{
+    let mut groups = options.groups();
+    if groups.is_empty() {
+        panic!("No files specified, use --globbing and/or --group")
+    }
+
+    let detected: Vec<_> = groups.iter_mut()
+        .map(|group| detect_group(group, options.no_detect))
+        .collect();
+
+    // the comment of the first group with files becomes the global comment
+    let global_comment = options.global_comment
+        .clone()
+        .or_else(
+            || groups.iter()
+                .zip(detected.iter())
+                .find(|(_, (paths, _))| !paths.is_empty())
+                .and_then(|(group, _)| group.comment.clone())
+        ).map(Comment::from);
+
+    let mut files = Vec::new();
+    for (group, (paths, skip_header_lines)) in groups.iter().zip(detected)
+    {
+        if paths.is_empty() {
+            eprintln!("WARNING: No files match {:?}", group.pattern);
+            continue;
+        }
+        files.extend(
+            group_files(group, paths, skip_header_lines, options.hist_col_right, &global_comment)
+        );
+    }
 
     let job = Job{
+        schema: None,
+        include: None,
+        variables: None,
         files,
         bin_size: options.bin_size,
         bin_starting_point: options.bin_starting_point,
         merge: options.merge,
         out: options.out,
-        global_comment: options.global_comment,
-        hist: HistType::HistIsizeFast
+        global_comment,
+        hist: HistType::HistIsizeFast,
+        output_format: options.output_format,
+        only_merged: None,
+        aligned_out: None,
+        provenance: None,
+        carry_prefix: None,
+        normalization: None,
+        intervals: Vec::new()
     };
 
     match options.job_file{
-        None => serde_json::to_writer_pretty(std::io::stdout(), &job),
+        None => JobFormat::Json.write_job(&job, std::io::stdout()),
         Some(file) => {
-            let f = File::create(file).expect("unable to create file");
+            let f = File::create(&file).expect("unable to create file");
             let buf = BufWriter::new(f);
-            serde_json::to_writer_pretty(buf, &job)
+            JobFormat::from_path(&file).write_job(&job, buf)
         }
     }.unwrap()
 }
 
+/// Prints the JSON Schema of the job file
+pub fn print_schema()
+{
+    let schema = schema_for!(Job);
+    serde_json::to_writer_pretty(std::io::stdout(), &schema).unwrap();
+    println!()
+}
+
 pub fn example()
 {
     let output = "output.dat";
@@ -114,7 +300,16 @@ pub fn example()
         log_cols: log_cols1,
         index_hist_right: None,
         shift: Some(23),
-        missing: None
+        missing: None,
+        format: None,
+        inline_comments: None,
+        skip_header_lines: None,
+        min_bin: Some(30),
+        max_bin: None,
+        auto_trim: None,
+        reference: Some(true),
+        enabled: None,
+        note: None
     };
 
     let mut log_cols2: Vec<_> = (3..5)
@@ -126,28 +321,56 @@ pub fn example()
         ).collect();
 
     log_cols2[1].trim_right = Some(14);
+    log_cols2[0].enabled = Some(false);
+    log_cols2[0].note = Some("not converged".to_owned());
 
     let file_info2 = FileInfo{
         path: "ABSOLUTE_PATH/file2.dat".to_owned(),
         index_hist_left: None,
-        comment: Some("%".to_owned()),
+        comment: Some(Comment::Multiple(vec!["%".to_owned(), "//".to_owned()])),
         sep: Some(",".to_owned()),
         log_cols: log_cols2,
         index_hist_right: Some(1),
         shift: None,
-        missing: Some("NONE".to_string())
+        missing: Some("NONE".to_string()),
+        format: None,
+        inline_comments: Some(true),
+        skip_header_lines: Some(2),
+        min_bin: None,
+        max_bin: None,
+        auto_trim: Some(
+            AutoTrim{
+                derivative_threshold: Some(0.5),
+                window: None,
+                hits_col: Some(2),
+                min_hits: None
+            }
+        ),
+        reference: None,
+        enabled: None,
+        note: None
     };
 
     let file_vec = vec![file_info1, file_info2];
 
     let job = Job{
+        schema: None,
+        include: None,
+        variables: None,
         out: output.to_owned(),
         files: file_vec,
         merge: MergeType::Average,
         hist: HistType::HistIsizeFast,
-        global_comment: Some("#".to_owned()),
+        global_comment: Some(Comment::from("#".to_owned())),
         bin_size: None,
-        bin_starting_point: None
+        bin_starting_point: None,
+        output_format: Some(OutputFormat::Text),
+        only_merged: Some(false),
+        aligned_out: Some("aligned.dat".to_owned()),
+        provenance: Some(true),
+        carry_prefix: Some("#CMD:".to_owned()),
+        normalization: Some(Normalization::Sum),
+        intervals: Vec::new()
     };
 
     serde_json::to_writer_pretty(std::io::stdout(), &job).unwrap();
@@ -156,23 +379,83 @@ pub fn example()
 
 
 impl Job{
-    pub fn work(&self) 
+    /// Copies the global comment into the files without their own comment and sorts the log columns.
+    /// Columns with `till_end` are expanded when the file is read, see [FileInfo::get_intervals]
+    pub(crate) fn prepare_files(&mut self)
     {
-        let mut container: Vec<_> = self.files.iter()
-            .flat_map(
-                |file_info|
-                {
-                    let (hist, log) = file_info.get_logs_and_hists();
-                    hist.into_iter()
-                        .zip(log.into_iter())
-                }
-            ).collect();
+        if let Some(comment) = &self.global_comment {
+            self.files.iter_mut()
+                .filter(|file_info| file_info.comment.is_none())
+                .for_each(
+                    |f|
+                    f.comment = Some(comment.clone())
+                );
+        }
+
+        self.files.iter_mut()
+            .for_each(FileInfo::sort_cols);
+    }
+
+    /// Add an in-memory interval, see [JobBuilder::interval]
+    pub fn add_interval(&mut self, left: isize, log_prob: Vec<f64>, reference: bool)
+    {
+        let name = format!("interval{}", self.intervals.len());
+        let mut interval = Interval::new(left, log_prob, name);
+        interval.info.reference = reference;
+        self.intervals.push(interval);
+    }
+
+    /// All intervals of the files and the in-memory intervals, 
+    /// sorted in the order in which they are glued
+    pub fn collect_intervals(&self) -> Vec<Interval>
+    {
+        self.read_intervals().0
+    }
+
+    /// [Job::collect_intervals] together with the lines starting with `carry_prefix`
+    fn read_intervals(&self) -> (Vec<Interval>, Vec<CarriedLines>)
+    {
+        let mut intervals = Vec::new();
+        let mut carried = Vec::new();
+        for file in self.files.iter()
+        {
+            let (file_intervals, lines) = file.get_intervals_carrying(self.carry_prefix.as_deref());
+            intervals.extend(file_intervals);
+            if !lines.is_empty() {
+                carried.push(
+                    CarriedLines{
+                        path: file.path.clone(),
+                        lines
+                    }
+                );
+            }
+        }
+        intervals.extend(self.intervals.iter().cloned());
 
         // now I have to sort them! Otherwise I might get glue errors
-        container
-            .sort_unstable_by(|a,b| a.0.left_compare(&b.0));
+        intervals
+            .sort_unstable_by(|a,b| a.hist.left_compare(&b.hist));
+        (intervals, carried)
+    }
 
-        let (hists, log_probs) = container.into_iter().unzip();
+    fn bin_starting_point(&self) -> f64
+    {
+        match (self.bin_size, self.bin_starting_point){
+            (Some(bin_size), None) => {
+                eprintln!("Warning: bin_size specified, but no bin_starting_point! Using bin_size as bin_starting_point");
+                bin_size
+            },
+            (_, Some(s)) => s,
+            (None, None) => 0.0
+        }
+    }
+
+    /// Glue the sorted intervals. The result is not normalized yet
+    fn glue_intervals(&self, intervals: &[Interval]) -> MergeResult
+    {
+        let (hists, log_probs) = intervals.iter()
+            .map(|i| (i.hist.clone(), i.log_prob.clone()))
+            .unzip();
 
         let glued = match self.merge
         {
@@ -183,27 +466,95 @@ impl Job{
                 sampling::glue::derivative_merged_and_aligned(log_probs, hists, LogBase::Base10)
             }
         }.expect("Unable to glue");
-        
 
-            
+        MergeResult::new(
+            glued.glued(),
+            glued.aligned(),
+            intervals,
+            self.bin_size,
+            self.bin_starting_point()
+        )
+    }
 
+    fn normalization(&self, intervals: &[Interval]) -> Option<Normalization>
+    {
+        match self.normalization{
+            None if intervals.iter().any(|i| i.info.reference) => Some(Normalization::Reference),
+            n => n
+        }
+    }
+
+    /// Merge everything and return the normalized result instead of writing it to `out`
+    pub fn glue(&self) -> MergeResult
+    {
+        let (intervals, carried) = self.read_intervals();
+        if intervals.is_empty() {
+            panic!("Nothing to merge, the job contains no intervals")
+        }
+        let mut result = self.glue_intervals(&intervals);
+        result.carried = carried;
+        result.normalize(self.normalization(&intervals), self.bin_size, &intervals);
+        result
+    }
+
+    /// Write the result into `out` (and `aligned_out`) as specified by this job
+    pub fn write(&self, result: &MergeResult)
+    {
         let output = File::create(&self.out)
             .expect("Unable to create output file");
-        let buf = BufWriter::new(output);
-
-        match self.bin_size{
-            None => glued.write(buf).unwrap(),
-            Some(bin_size) => {
-                let s = match self.bin_starting_point{
-                    Some(s) => s,
-                    None => {
-                        eprintln!("Warning: bin_size specified, but no bin_starting_point! Using bin_size as bin_starting_point");
-                        bin_size
-                    }
-                };
-                glued.write_rescaled(buf, bin_size, s).unwrap()
+        let mut buf = BufWriter::new(output);
+
+        let format = self.output_format.unwrap_or_default();
+
+        let provenance = match (self.provenance, format) {
+            (Some(true), OutputFormat::Npy) => {
+                eprintln!("Warning: Npy does not support the provenance header");
+                false
+            },
+            (Some(p), _) => p,
+            (None, OutputFormat::Csv | OutputFormat::Npy) => false,
+            (None, _) => true
+        };
+        let metadata = Provenance::new(self, result, provenance);
+
+        if let Some(aligned_out) = &self.aligned_out {
+            let file = File::create(aligned_out)
+                .expect("Unable to create output file for aligned intervals");
+            let csv = matches!(format, OutputFormat::Csv);
+            let mut writer = BufWriter::new(file);
+            if provenance {
+                metadata.write_header(&mut writer)
+                    .unwrap();
             }
+            result.write_long(writer, csv)
+                .unwrap();
         }
-        
+
+        let mut merged_only;
+        let result = if self.only_merged.unwrap_or(false) {
+            merged_only = result.clone();
+            merged_only.only_merged();
+            &merged_only
+        } else {
+            result
+        };
+
+        if provenance && matches!(format, OutputFormat::Text | OutputFormat::Csv) {
+            metadata.write_header(&mut buf)
+                .unwrap();
+        }
+
+        match format{
+            OutputFormat::Text => result.write_text(buf).unwrap(),
+            OutputFormat::Csv => result.write_csv(buf).unwrap(),
+            OutputFormat::Json => result.write_json(buf, &metadata).unwrap(),
+            OutputFormat::Npy => result.write_npy(buf).unwrap()
+        }
+    }
+
+    pub fn work(&self) 
+    {
+        let result = self.glue();
+        self.write(&result);
     }
-}
\ No newline at end of file
+}